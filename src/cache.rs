@@ -0,0 +1,99 @@
+use std::{
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use tracing::debug;
+
+/// Cached validators of a previous response
+#[derive(Debug, Clone)]
+pub(crate) struct Entry {
+    /// `ETag` header of the response
+    pub etag: Option<String>,
+    /// `Last-Modified` header of the response
+    pub last_modified: Option<String>,
+    /// Path of the cached content
+    pub body: PathBuf,
+}
+
+/// Local response cache, remembers the `ETag` and `Last-Modified` of each url
+/// and the content, so the next request can be conditional
+#[derive(Debug)]
+pub struct HttpCache {
+    dir: PathBuf,
+}
+impl HttpCache {
+    /// Create a new cache in a directory
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+    /// Get the cached entry of the url, if the entry and the content exists
+    pub(crate) fn get(&self, url: &str) -> Option<Entry> {
+        let key = key(url);
+        let meta = fs::read_to_string(self.dir.join(format!("{key}.meta"))).ok()?;
+        let body = self.dir.join(format!("{key}.body"));
+        if !body.exists() {
+            return None;
+        }
+        let mut entry = Entry {
+            etag: None,
+            last_modified: None,
+            body,
+        };
+        for line in meta.lines() {
+            match line.split_once(' ') {
+                // Ignore the entry if the key collides with other url
+                Some(("url", value)) if value != url => return None,
+                Some(("etag", value)) => entry.etag = Some(value.to_string()),
+                Some(("last-modified", value)) => entry.last_modified = Some(value.to_string()),
+                _ => {}
+            }
+        }
+        if entry.etag.is_none() && entry.last_modified.is_none() {
+            return None;
+        }
+        Some(entry)
+    }
+    /// Save the validators and the content of the url
+    pub(crate) fn put(
+        &self,
+        url: &str,
+        etag: Option<&str>,
+        last_modified: Option<&str>,
+        content: &mut dyn Read,
+    ) -> Result<(), String> {
+        if etag.is_none() && last_modified.is_none() {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.dir).map_err(|e| e.to_string())?;
+        let key = key(url);
+        let mut meta = format!("url {url}\n");
+        if let Some(etag) = etag {
+            meta.push_str(&format!("etag {etag}\n"));
+        }
+        if let Some(last_modified) = last_modified {
+            meta.push_str(&format!("last-modified {last_modified}\n"));
+        }
+        // Write the body first, so a meta file never points to a partial body
+        let mut body = tempfile::NamedTempFile::new_in(&self.dir).map_err(|e| e.to_string())?;
+        io::copy(content, &mut body).map_err(|e| e.to_string())?;
+        body.persist(self.dir.join(format!("{key}.body")))
+            .map_err(|e| e.to_string())?;
+        fs::write(self.dir.join(format!("{key}.meta")), meta).map_err(|e| e.to_string())?;
+        debug!("Cached response of {url}");
+        Ok(())
+    }
+}
+
+/// Stable key of an url (FNV-1a), the std hasher is not stable between releases
+fn key(url: &str) -> String {
+    let mut hash = 0xcbf29ce484222325u64;
+    for byte in url.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hex::encode(hash.to_be_bytes())
+}
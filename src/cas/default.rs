@@ -1,37 +1,441 @@
-use tracing::debug;
+use tracing::{debug, info, warn};
 
-use super::Store;
-use crate::hash::Hasher;
+use super::{LinkMode, Store};
+use crate::{
+    CHUNK_SIZE,
+    hash::{Digest, Hasher, algorithm::HashAlgorithm, digest_reader},
+};
+use fs4::fs_std::FileExt;
 use std::{
     fs,
     hash::DefaultHasher,
+    io::{self, BufReader, Read, Write},
     path::{Path, PathBuf},
+    sync::OnceLock,
 };
+use tempfile::NamedTempFile;
+
+/// Name of the metadata file of the store, in the base path
+pub const METADATA: &str = "metadata";
+/// Name of the legacy algorithm in the metadata, the [std::hash::DefaultHasher]
+const LEGACY: &str = "default";
+/// Name of the registry of the roots, the paths linked to the objects, in the base path
+pub const ROOTS: &str = "roots";
+/// Name of the lock of the registry of the roots, shared by the processes, in the base path
+const ROOTS_LOCK: &str = "roots.lock";
 
-/// Default implementation for store, use the [std::hash::DefaultHasher] for hashing
+/// Default implementation for store, the objects are named by the digest of the content,
+/// sha256 by default. The algorithm is recorded in the [METADATA] file of the store
 #[derive(Debug)]
 pub struct DefaultStore {
     base: PathBuf,
+    /// Algorithm of the names, `None` is the legacy [std::hash::DefaultHasher]
+    algorithm: Option<HashAlgorithm>,
+    /// Error of a new store without the default algorithm, returned by the writes
+    unavailable: Option<String>,
+    /// The metadata was checked, done before the first write until it succeeds
+    checked: OnceLock<()>,
+    /// How the objects are materialized
+    link_mode: LinkMode,
 }
 impl DefaultStore {
-    /// Create a new store with a base path
+    /// Create a new store with a base path, hashing with sha256.
+    /// Without the `sha` feature the writes fail, use [DefaultStore::new_with_algorithm]
     pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        match default_algorithm() {
+            Ok(algorithm) => Self::new_with_algorithm(path, algorithm),
+            Err(err) => Self {
+                unavailable: Some(err),
+                ..Self::with_base(path, None)
+            },
+        }
+    }
+    /// Create a new store with a base path, hashing with the algorithm
+    pub fn new_with_algorithm<P: AsRef<Path>>(path: P, algorithm: HashAlgorithm) -> Self {
+        Self::with_base(path, Some(algorithm))
+    }
+    /// Open an existing store with the algorithm recorded in the metadata,
+    /// a store without objects nor metadata uses sha256
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let mut store = Self::with_base(path, None);
+        store.algorithm = match store.recorded()? {
+            Some(algorithm) => algorithm,
+            None if store.has_objects() => None,
+            None => Some(default_algorithm()?),
+        };
+        Ok(store)
+    }
+    /// Store in a base path with the algorithm, `None` is the legacy hasher
+    fn with_base<P: AsRef<Path>>(path: P, algorithm: Option<HashAlgorithm>) -> Self {
         Self {
             base: path.as_ref().to_path_buf(),
+            algorithm,
+            unavailable: None,
+            checked: OnceLock::new(),
+            link_mode: LinkMode::default(),
         }
     }
-}
-impl Store for DefaultStore {
-    fn write(&self, file: Vec<u8>) -> Result<PathBuf, String> {
-        let hash = DefaultHasher::compute(&file)?;
-        let prefix = &hash[0..2];
-        let parent = self.base.join(prefix);
+    /// Sets how the objects are materialized at the paths of the files
+    pub fn with_link_mode(mut self, mode: LinkMode) -> Self {
+        self.link_mode = mode;
+        self
+    }
+    /// Sets the hash algorithm of the object names
+    pub fn with_algorithm(mut self, algorithm: HashAlgorithm) -> Self {
+        self.algorithm = Some(algorithm);
+        self.unavailable = None;
+        self
+    }
+    /// Rehash the objects written with other algorithm, recorded in the metadata,
+    /// or the legacy hasher if the store has objects without metadata.
+    /// The registered roots and the symlinks inside `links` that point to the old
+    /// objects are updated.
+    /// Returns the old and new path of every moved object
+    pub fn migrate<P: AsRef<Path>>(&self, links: &[P]) -> Result<Vec<(PathBuf, PathBuf)>, String> {
+        if let Some(err) = &self.unavailable {
+            return Err(err.clone());
+        }
+        let from = self.current()?;
+        if from == self.algorithm {
+            self.write_metadata()?;
+            return Ok(Vec::new());
+        }
+        info!(
+            "Migrating the store {} from {} to {}",
+            self.base.to_string_lossy(),
+            name(from),
+            name(self.algorithm)
+        );
+        let mut moved = Vec::new();
+        for old in self.objects()? {
+            let mut file = fs::File::open(&old).map_err(|e| e.to_string())?;
+            let new = self.persist(&mut file)?;
+            if new != old {
+                moved.push((old, new));
+            }
+        }
+        let resolved: Vec<(PathBuf, &PathBuf)> = moved
+            .iter()
+            .filter_map(|(old, new)| Some((fs::canonicalize(old).ok()?, new)))
+            .collect();
+        // The registered roots are relinked too, the registry is locked until the
+        // old objects are removed
+        let _lock = self.lock_roots()?;
+        for root in self.roots()? {
+            if root.symlink_metadata().is_ok_and(|m| m.is_symlink()) {
+                relink_symlink(&root, &resolved)?;
+            }
+        }
+        for dir in links {
+            relink(dir.as_ref(), &resolved)?;
+        }
+        // The old objects are removed once every new object is written and linked
+        for (old, _) in &moved {
+            fs::remove_file(old).map_err(|e| e.to_string())?;
+        }
+        self.write_metadata()?;
+        Ok(moved)
+    }
+    /// Digest of a content with the algorithm of the store
+    pub fn digest(&self, file: &[u8]) -> Digest {
+        let mut hasher = self.hasher();
+        hasher.update(file);
+        hasher.finalize()
+    }
+    /// New hasher of the algorithm of the store
+    pub(crate) fn hasher(&self) -> Box<dyn Hasher> {
+        match self.algorithm {
+            Some(algorithm) => algorithm.hasher(),
+            None => Box::new(<DefaultHasher as Hasher>::new()),
+        }
+    }
+    /// Path of the object of a digest, creating the prefix directory
+    fn object_of(&self, digest: &Digest) -> Result<PathBuf, String> {
+        let hash = digest.to_hex();
+        let parent = self.base.join(&hash[0..2]);
         if !parent.exists() {
             fs::create_dir_all(&parent).map_err(|e| e.to_string())?;
         }
-        let path = parent.join(hash);
+        Ok(parent.join(hash))
+    }
+    /// Copy the content into a temp file of the store while is hashed,
+    /// renamed to the object path at the end, without checking the metadata
+    fn persist(&self, reader: &mut dyn Read) -> Result<PathBuf, String> {
+        fs::create_dir_all(&self.base).map_err(|e| e.to_string())?;
+        let mut temp = NamedTempFile::new_in(&self.base).map_err(|e| e.to_string())?;
+        let mut hasher = self.hasher();
+        let mut buffer = vec![0u8; CHUNK_SIZE];
+        loop {
+            let size = match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(size) => size,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.to_string()),
+            };
+            hasher.update(&buffer[..size]);
+            temp.write_all(&buffer[..size]).map_err(|e| e.to_string())?;
+        }
+        let path = self.object_of(&hasher.finalize())?;
+        // The content is already in the store
+        if path.exists() {
+            return Ok(path);
+        }
         debug!("Writing file in {}", path.to_string_lossy());
-        fs::write(&path, file).map_err(|e| e.to_string())?;
+        temp.persist(&path).map_err(|e| e.to_string())?;
         Ok(path)
     }
+    /// Algorithm of the existing objects, the legacy hasher if there is no metadata
+    fn current(&self) -> Result<Option<HashAlgorithm>, String> {
+        Ok(match self.recorded()? {
+            Some(algorithm) => algorithm,
+            None if self.has_objects() => None,
+            None => self.algorithm,
+        })
+    }
+    /// Algorithm recorded in the metadata, `None` if there is no metadata
+    fn recorded(&self) -> Result<Option<Option<HashAlgorithm>>, String> {
+        let content = match fs::read_to_string(self.base.join(METADATA)) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.to_string()),
+        };
+        let algorithm = content
+            .lines()
+            .find_map(|line| line.strip_prefix("algorithm="))
+            .ok_or("Invalid store metadata, missing the algorithm")?
+            .trim();
+        match algorithm {
+            LEGACY => Ok(Some(None)),
+            algorithm => Ok(Some(Some(algorithm.parse()?))),
+        }
+    }
+    /// Record the algorithm in the metadata
+    fn write_metadata(&self) -> Result<(), String> {
+        fs::create_dir_all(&self.base).map_err(|e| e.to_string())?;
+        let metadata = format!("version=1\nalgorithm={}\n", name(self.algorithm));
+        fs::write(self.base.join(METADATA), metadata).map_err(|e| e.to_string())
+    }
+    /// Check the metadata until succeeds, a migration can fix a failed check
+    fn ensure_checked(&self) -> Result<(), String> {
+        if self.checked.get().is_none() {
+            self.check()?;
+            let _ = self.checked.set(());
+        }
+        Ok(())
+    }
+    /// Check that the store uses the same algorithm, recording it in a new store
+    fn check(&self) -> Result<(), String> {
+        if let Some(err) = &self.unavailable {
+            return Err(err.clone());
+        }
+        let recorded = match self.recorded()? {
+            Some(recorded) => recorded,
+            None if self.has_objects() => {
+                warn!(
+                    "The store {} has objects without metadata",
+                    self.base.to_string_lossy()
+                );
+                None
+            }
+            None => return self.write_metadata(),
+        };
+        if recorded != self.algorithm {
+            return Err(format!(
+                "The store {} uses {}, not {}, migrate it",
+                self.base.to_string_lossy(),
+                name(recorded),
+                name(self.algorithm)
+            ));
+        }
+        Ok(())
+    }
+    /// Paths of the registry of the roots
+    pub(crate) fn roots(&self) -> Result<Vec<PathBuf>, String> {
+        match fs::read_to_string(self.base.join(ROOTS)) {
+            Ok(content) => Ok(content.lines().map(PathBuf::from).collect()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+    /// Lock the registry of the roots, between the threads and the processes.
+    /// The lock is released when the file is dropped
+    pub(crate) fn lock_roots(&self) -> Result<fs::File, String> {
+        fs::create_dir_all(&self.base).map_err(|e| e.to_string())?;
+        let lock = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.base.join(ROOTS_LOCK))
+            .map_err(|e| e.to_string())?;
+        lock.lock_exclusive().map_err(|e| e.to_string())?;
+        Ok(lock)
+    }
+    /// Replace the registry of the roots with a rename, the registry must be locked
+    pub(crate) fn write_roots(&self, roots: &[PathBuf]) -> Result<(), String> {
+        let mut temp = NamedTempFile::new_in(&self.base).map_err(|e| e.to_string())?;
+        for root in roots {
+            writeln!(temp, "{}", root.to_string_lossy()).map_err(|e| e.to_string())?;
+        }
+        temp.persist(self.base.join(ROOTS))
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+    /// Check if the store has objects
+    fn has_objects(&self) -> bool {
+        self.objects().is_ok_and(|objects| !objects.is_empty())
+    }
+    /// Every object of the store
+    pub(crate) fn objects(&self) -> Result<Vec<PathBuf>, String> {
+        let mut objects = Vec::new();
+        let Ok(dirs) = fs::read_dir(&self.base) else {
+            return Ok(objects);
+        };
+        for dir in dirs {
+            let dir = dir.map_err(|e| e.to_string())?;
+            if !dir.file_type().is_ok_and(|t| t.is_dir()) || dir.file_name().len() != 2 {
+                continue;
+            }
+            for object in fs::read_dir(dir.path()).map_err(|e| e.to_string())? {
+                objects.push(object.map_err(|e| e.to_string())?.path());
+            }
+        }
+        Ok(objects)
+    }
+}
+impl Store for DefaultStore {
+    fn write(&self, file: Vec<u8>) -> Result<PathBuf, String> {
+        self.write_reader(&mut file.as_slice())
+    }
+    /// Hash the content while is copied into a temp file of the store,
+    /// renamed to the object path at the end
+    fn write_reader(&self, reader: &mut dyn Read) -> Result<PathBuf, String> {
+        self.ensure_checked()?;
+        self.persist(reader)
+    }
+    /// Rename the file to the object path, or copy it if the file is in other filesystem
+    fn adopt(&self, file: &Path) -> Result<PathBuf, String> {
+        self.ensure_checked()?;
+        let reader = fs::File::open(file).map_err(|e| e.to_string())?;
+        let digest =
+            digest_reader(self.hasher(), BufReader::new(reader)).map_err(|e| e.to_string())?;
+        let path = self.object_of(&digest)?;
+        if path.exists() {
+            fs::remove_file(file).map_err(|e| e.to_string())?;
+            return Ok(path);
+        }
+        debug!(
+            "Adopting {} in {}",
+            file.to_string_lossy(),
+            path.to_string_lossy()
+        );
+        if fs::rename(file, &path).is_err() {
+            let mut reader = fs::File::open(file).map_err(|e| e.to_string())?;
+            self.write_reader(&mut reader)?;
+            fs::remove_file(file).map_err(|e| e.to_string())?;
+        }
+        Ok(path)
+    }
+    fn root(&self) -> Option<&Path> {
+        Some(&self.base)
+    }
+    fn link_mode(&self) -> LinkMode {
+        self.link_mode
+    }
+    /// Append the path to the [ROOTS] registry, if isn't registered yet
+    fn register_root(&self, path: &Path) -> Result<(), String> {
+        let path = std::path::absolute(path).map_err(|e| e.to_string())?;
+        let _lock = self.lock_roots()?;
+        if self.roots()?.contains(&path) {
+            return Ok(());
+        }
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.base.join(ROOTS))
+            .and_then(|mut roots| writeln!(roots, "{}", path.to_string_lossy()))
+            .map_err(|e| e.to_string())
+    }
+    /// Algorithm of the object names, `None` is the legacy [std::hash::DefaultHasher]
+    fn algorithm(&self) -> Option<HashAlgorithm> {
+        self.algorithm
+    }
+    fn path(&self, digest: &Digest) -> Option<PathBuf> {
+        let hash = digest.to_hex();
+        let path = self.base.join(hash.get(0..2)?).join(&hash);
+        path.is_file().then_some(path)
+    }
+    fn remove(&self, digest: &Digest) -> Result<bool, String> {
+        let Some(path) = self.path(digest) else {
+            return Ok(false);
+        };
+        debug!("Removing {}", path.to_string_lossy());
+        fs::remove_file(&path).map_err(|e| e.to_string())?;
+        // The prefix directory is removed when it is empty
+        if let Some(parent) = path.parent() {
+            let _ = fs::remove_dir(parent);
+        }
+        Ok(true)
+    }
+    fn iter(&self) -> Box<dyn Iterator<Item = Result<Digest, String>> + '_> {
+        match self.objects() {
+            Ok(objects) => Box::new(objects.into_iter().map(|object| {
+                let name = object.file_name().unwrap_or_default().to_string_lossy();
+                hex::decode(name.as_ref())
+                    .map(Digest::new)
+                    .map_err(|_| format!("Invalid object {}", object.to_string_lossy()))
+            })),
+            Err(err) => Box::new(std::iter::once(Err(err))),
+        }
+    }
+}
+
+/// Algorithm of the new stores, sha256
+#[cfg(feature = "sha")]
+fn default_algorithm() -> Result<HashAlgorithm, String> {
+    Ok(HashAlgorithm::Sha256)
+}
+
+/// Without the `sha` feature the algorithm of a new store must be explicit
+#[cfg(not(feature = "sha"))]
+fn default_algorithm() -> Result<HashAlgorithm, String> {
+    Err(String::from(
+        "A new store requires the sha feature, or an explicit algorithm",
+    ))
+}
+
+/// Name of the algorithm in the metadata
+fn name(algorithm: Option<HashAlgorithm>) -> &'static str {
+    algorithm.map_or(LEGACY, |algorithm| algorithm.name())
+}
+
+/// Update the symlinks of a directory that point to the moved objects,
+/// keeping the relative symlinks relative
+fn relink(dir: &Path, moved: &[(PathBuf, &PathBuf)]) -> Result<(), String> {
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let file_type = entry.file_type().map_err(|e| e.to_string())?;
+        if file_type.is_dir() {
+            relink(&entry.path(), moved)?;
+        } else if file_type.is_symlink() {
+            relink_symlink(&entry.path(), moved)?;
+        }
+    }
+    Ok(())
+}
+
+/// Update a symlink that points to a moved object, keeping a relative symlink relative
+fn relink_symlink(link: &Path, moved: &[(PathBuf, &PathBuf)]) -> Result<(), String> {
+    let target = fs::read_link(link).map_err(|e| e.to_string())?;
+    let parent = link.parent().unwrap_or(Path::new(""));
+    let Ok(resolved) = fs::canonicalize(parent.join(&target)) else {
+        return Ok(());
+    };
+    if let Some((_, new)) = moved.iter().find(|(old, _)| *old == resolved) {
+        let mode = match target.is_relative() {
+            true => LinkMode::RelativeSymlink,
+            false => LinkMode::Symlink,
+        };
+        super::link(new, link, mode)?;
+    }
+    Ok(())
 }
@@ -0,0 +1,146 @@
+use std::{
+    collections::HashSet,
+    fs,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+use tracing::{debug, info};
+
+use super::{Store, default::DefaultStore};
+use crate::hash::digest_reader;
+
+/// Options of a garbage collection
+#[derive(Debug, Clone, Default)]
+pub struct Gc {
+    /// Only report the objects, without removing them
+    dry_run: bool,
+    /// Directories scanned for symlinks to the objects
+    scan: Vec<PathBuf>,
+}
+impl Gc {
+    /// Create the options, the roots are the registered paths
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Only report the unreferenced objects and the reclaimable bytes
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+    /// Add a directory scanned for symlinks to the objects, as the roots
+    /// created without registry
+    pub fn with_scan_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.scan.push(dir.as_ref().to_path_buf());
+        self
+    }
+}
+
+/// Result of a garbage collection
+#[derive(Debug, Clone, Default)]
+pub struct GcReport {
+    /// Unreferenced objects, removed unless is a dry run
+    pub removed: Vec<PathBuf>,
+    /// Bytes of the removed objects
+    pub reclaimed: u64,
+    /// Referenced objects
+    pub kept: usize,
+    /// Registered roots that don't reference an object anymore, removed from the registry
+    pub pruned_roots: usize,
+}
+
+impl DefaultStore {
+    /// Remove the objects not referenced by a root. A root references the object
+    /// that the symlink points to, or that has the same content as a hardlink or a copy.
+    /// Shouldn't run while files are downloaded to the store.
+    /// The registry of the roots is locked during the collection
+    pub fn gc(&self, gc: &Gc) -> Result<GcReport, String> {
+        let _lock = self.lock_roots()?;
+        let objects: HashSet<PathBuf> = self
+            .objects()?
+            .into_iter()
+            .filter_map(|object| fs::canonicalize(object).ok())
+            .collect();
+        let mut referenced = HashSet::new();
+        let mut roots = Vec::new();
+        let mut seen = HashSet::new();
+        let mut pruned_roots = 0;
+        for root in self.roots()? {
+            match self
+                .referenced(&root)
+                .filter(|object| objects.contains(object))
+            {
+                Some(object) if seen.insert(root.clone()) => {
+                    referenced.insert(object);
+                    roots.push(root);
+                }
+                Some(_) => {}
+                None => {
+                    debug!("Pruning root {}", root.to_string_lossy());
+                    pruned_roots += 1;
+                }
+            }
+        }
+        for dir in &gc.scan {
+            scan(dir, &mut referenced)?;
+        }
+
+        let mut report = GcReport {
+            pruned_roots,
+            ..Default::default()
+        };
+        let mut objects: Vec<PathBuf> = objects.into_iter().collect();
+        objects.sort();
+        for object in objects {
+            if referenced.contains(&object) {
+                report.kept += 1;
+                continue;
+            }
+            report.reclaimed += fs::metadata(&object).map_or(0, |m| m.len());
+            if !gc.dry_run {
+                debug!("Removing {}", object.to_string_lossy());
+                fs::remove_file(&object).map_err(|e| e.to_string())?;
+                // The prefix directory is removed when it is empty
+                if let Some(parent) = object.parent() {
+                    let _ = fs::remove_dir(parent);
+                }
+            }
+            report.removed.push(object);
+        }
+        if !gc.dry_run {
+            self.write_roots(&roots)?;
+        }
+        info!(
+            "Garbage collection: {} objects, {} bytes reclaimed",
+            report.removed.len(),
+            report.reclaimed
+        );
+        Ok(report)
+    }
+    /// Object referenced by a root, the target of a symlink or the object with the same content
+    fn referenced(&self, root: &Path) -> Option<PathBuf> {
+        let metadata = root.symlink_metadata().ok()?;
+        if metadata.is_symlink() {
+            return fs::canonicalize(root).ok();
+        }
+        let file = fs::File::open(root).ok()?;
+        let digest = digest_reader(self.hasher(), BufReader::new(file)).ok()?;
+        fs::canonicalize(self.path(&digest)?).ok()
+    }
+}
+
+/// Add the targets of the symlinks of a directory
+fn scan(dir: &Path, referenced: &mut HashSet<PathBuf>) -> Result<(), String> {
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let file_type = entry.file_type().map_err(|e| e.to_string())?;
+        if file_type.is_dir() {
+            scan(&entry.path(), referenced)?;
+        } else if file_type.is_symlink()
+            && let Ok(target) = fs::canonicalize(entry.path())
+        {
+            referenced.insert(target);
+        }
+    }
+    Ok(())
+}
@@ -1,8 +1,36 @@
-use std::{fmt::Debug, fs, path::PathBuf};
+use std::{
+    fmt::Debug,
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+};
 
 use tracing::debug;
 
+use crate::hash::{Digest, algorithm::HashAlgorithm};
+
 pub mod default;
+/// Garbage collection of the unreferenced objects
+pub mod gc;
+
+/// How an object of the store is materialized at the path of a file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LinkMode {
+    /// Symlink with the absolute path of the object
+    #[default]
+    Symlink,
+    /// Symlink with the path of the object relative to the file,
+    /// valid while the store and the files move together
+    RelativeSymlink,
+    /// Hardlink, the store and the file must be in the same filesystem.
+    /// A modification of the file changes the object
+    Hardlink,
+    /// Copy-on-write clone of the object, a copy if the filesystem doesn't support it.
+    /// Requires the `reflink` feature, otherwise is always a copy
+    Reflink,
+    /// Plain copy of the object
+    Copy,
+}
 
 /// Define a new store that manage a cas system
 /// Implements:
@@ -12,18 +40,120 @@ pub mod default;
 pub trait Store: Send + Sync + Debug {
     /// Write a new file in hash filesystem and return the path
     fn write(&self, file: Vec<u8>) -> Result<PathBuf, String>;
-    /// Write a new file and create a symlink to the original path
+    /// Write the content of a reader and return the path, by default the content is buffered
+    fn write_reader(&self, reader: &mut dyn Read) -> Result<PathBuf, String> {
+        let mut file = Vec::new();
+        reader.read_to_end(&mut file).map_err(|e| e.to_string())?;
+        self.write(file)
+    }
+    /// Move an existing file into the store and return the path of the object
+    fn adopt(&self, file: &Path) -> Result<PathBuf, String> {
+        let mut reader = fs::File::open(file).map_err(|e| e.to_string())?;
+        let path = self.write_reader(&mut reader)?;
+        fs::remove_file(file).map_err(|e| e.to_string())?;
+        Ok(path)
+    }
+    /// Directory where the objects are written, if the store is in the filesystem
+    fn root(&self) -> Option<&Path> {
+        None
+    }
+    /// Path of the object of a digest, if the store has the object
+    fn path(&self, _digest: &Digest) -> Option<PathBuf> {
+        None
+    }
+    /// Check if the store has the object of a digest
+    fn contains(&self, digest: &Digest) -> bool {
+        self.path(digest).is_some()
+    }
+    /// Open the object of a digest
+    fn open(&self, digest: &Digest) -> Result<Box<dyn Read + Send>, String> {
+        let path = self
+            .path(digest)
+            .ok_or(format!("The store has not the object {digest}"))?;
+        Ok(Box::new(fs::File::open(path).map_err(|e| e.to_string())?))
+    }
+    /// Remove the object of a digest, returns false if the store has not the object
+    fn remove(&self, digest: &Digest) -> Result<bool, String> {
+        match self.path(digest) {
+            Some(path) => fs::remove_file(path)
+                .map_err(|e| e.to_string())
+                .map(|_| true),
+            None => Ok(false),
+        }
+    }
+    /// Digests of every object of the store
+    fn iter(&self) -> Box<dyn Iterator<Item = Result<Digest, String>> + '_> {
+        Box::new(std::iter::empty())
+    }
+    /// Write a new file and materialize it at the original path with the [LinkMode] of the store
     fn create(&self, file: Vec<u8>, dst: PathBuf) -> Result<(), String> {
         let src = self.write(file)?;
-        debug!(
-            "Creating symlink {} -> {}",
-            dst.to_string_lossy(),
-            src.to_string_lossy()
-        );
-        if !dst.parent().unwrap_or(&dst.clone()).exists() {
-            fs::create_dir_all(&dst.parent().unwrap_or(&dst.clone())).map_err(|e| e.to_string())?;
-        }
-        crate::utils::create_symlink(src, dst).map_err(|e| e.to_string())?;
+        link(&src, &dst, self.link_mode())?;
+        self.register_root(&dst)
+    }
+    /// Record a path materialized from an object, the object is kept by the garbage collection
+    fn register_root(&self, _path: &Path) -> Result<(), String> {
         Ok(())
     }
+    /// How the objects are materialized, default an absolute symlink
+    fn link_mode(&self) -> LinkMode {
+        LinkMode::default()
+    }
+    /// Algorithm of the digests of the objects, if the store is keyed by a known algorithm
+    fn algorithm(&self) -> Option<HashAlgorithm> {
+        None
+    }
+}
+
+/// Materialize an object at a path, creating the parent directories and replacing the existing file
+pub(crate) fn link(src: &Path, dst: &Path, mode: LinkMode) -> Result<(), String> {
+    debug!(
+        "Linking {} -> {} ({mode:?})",
+        dst.to_string_lossy(),
+        src.to_string_lossy()
+    );
+    if let Some(parent) = dst.parent()
+        && !parent.as_os_str().is_empty()
+        && !parent.exists()
+    {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    if dst.symlink_metadata().is_ok() {
+        fs::remove_file(dst).map_err(|e| e.to_string())?;
+    }
+    match mode {
+        LinkMode::Symlink => {
+            let src = fs::canonicalize(src).map_err(|e| e.to_string())?;
+            crate::utils::create_symlink(src, dst)
+        }
+        LinkMode::RelativeSymlink => crate::utils::create_symlink(relative(src, dst)?, dst),
+        LinkMode::Hardlink => fs::hard_link(src, dst),
+        #[cfg(feature = "reflink")]
+        LinkMode::Reflink => reflink_copy::reflink_or_copy(src, dst).map(|_| ()),
+        #[cfg(not(feature = "reflink"))]
+        LinkMode::Reflink => fs::copy(src, dst).map(|_| ()),
+        LinkMode::Copy => fs::copy(src, dst).map(|_| ()),
+    }
+    .map_err(|e| e.to_string())
+}
+
+/// Path of an object relative to the directory of a file
+fn relative(src: &Path, dst: &Path) -> Result<PathBuf, String> {
+    let src = fs::canonicalize(src).map_err(|e| e.to_string())?;
+    let dir = match dst.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let dir = fs::canonicalize(dir).map_err(|e| e.to_string())?;
+    let common = src
+        .components()
+        .zip(dir.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut relative = PathBuf::new();
+    for _ in dir.components().skip(common) {
+        relative.push("..");
+    }
+    relative.extend(src.components().skip(common));
+    Ok(relative)
 }
@@ -0,0 +1,121 @@
+use crate::hash::{Hash, algorithm::HashAlgorithm};
+
+/// Entry of a checksum file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChecksumEntry {
+    /// File name of the entry, empty if the checksum file only has the digest
+    pub name: String,
+    /// Algorithm of the digest, if is known
+    pub algorithm: Option<HashAlgorithm>,
+    /// Digest in hex
+    pub digest: String,
+}
+impl ChecksumEntry {
+    /// Create the hash check of the entry
+    pub fn hash(&self) -> Result<Hash, String> {
+        let algorithm = self
+            .algorithm
+            .ok_or(format!("Unknown hash algorithm of {}", self.name))?;
+        Hash::from_algorithm(algorithm, &self.digest)
+    }
+}
+
+/// Parse a checksum file in GNU coreutils (`<digest>  <name>`) or BSD (`SHA256 (<name>) = <digest>`) format.
+/// The algorithm of the GNU lines is inferred from the name of the checksum file
+/// (`SHA512SUMS`, `asset.sha256`) or the length of the digest
+pub fn parse(content: &str, source: &str) -> Vec<ChecksumEntry> {
+    let source_algorithm = algorithm_of_source(source);
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            parse_bsd(line).or_else(|| {
+                let mut entry = parse_gnu(line)?;
+                entry.algorithm = source_algorithm.or(algorithm_of_length(entry.digest.len()));
+                Some(entry)
+            })
+        })
+        .collect()
+}
+
+/// Find the entry of a file, comparing the file names
+pub fn find<'a, S: AsRef<str>>(
+    entries: &'a [ChecksumEntry],
+    names: &[S],
+) -> Option<&'a ChecksumEntry> {
+    let basename = |name: &'a str| name.rsplit(['/', '\\']).next().unwrap_or(name);
+    entries
+        .iter()
+        .find(|entry| {
+            names
+                .iter()
+                .any(|name| basename(&entry.name) == name.as_ref())
+        })
+        // A checksum file of a single asset may only have the digest
+        .or(match entries {
+            [entry] if entry.name.is_empty() => Some(entry),
+            _ => None,
+        })
+}
+
+/// Parse a BSD line, `SHA256 (name) = digest`
+fn parse_bsd(line: &str) -> Option<ChecksumEntry> {
+    let (algorithm, rest) = line.split_once(" (")?;
+    let (name, digest) = rest.rsplit_once(") = ")?;
+    let digest = digest.trim();
+    is_hex(digest).then(|| ChecksumEntry {
+        name: name.to_string(),
+        algorithm: algorithm.trim().parse().ok(),
+        digest: digest.to_ascii_lowercase(),
+    })
+}
+
+/// Parse a GNU line, `digest  name` or `digest *name` in binary mode
+fn parse_gnu(line: &str) -> Option<ChecksumEntry> {
+    // The lines of the names with special characters start with a backslash
+    let line = line.strip_prefix('\\').unwrap_or(line);
+    let (digest, name) = match line.split_once(char::is_whitespace) {
+        Some((digest, name)) => (digest, name.trim_start()),
+        None => (line, ""),
+    };
+    let name = name.strip_prefix('*').unwrap_or(name);
+    is_hex(digest).then(|| ChecksumEntry {
+        name: name.to_string(),
+        algorithm: None,
+        digest: digest.to_ascii_lowercase(),
+    })
+}
+
+/// Algorithm in the name of a checksum file, as `SHA256SUMS` or `asset.sha512`
+fn algorithm_of_source(source: &str) -> Option<HashAlgorithm> {
+    let name = source.split(['?', '#']).next().unwrap_or(source);
+    let name = name.rsplit('/').next().unwrap_or(name);
+    let normalize = |name: &str| name.replace(['-', '_'], "").to_ascii_lowercase();
+    let name = normalize(name);
+    let mut algorithms = HashAlgorithm::ALL.to_vec();
+    // The longer names first, `sha512256` before `sha512`
+    algorithms.sort_by_key(|algorithm| std::cmp::Reverse(algorithm.name().len()));
+    algorithms
+        .into_iter()
+        .find(|algorithm| name.contains(&normalize(algorithm.name())))
+}
+
+/// Algorithm of the usual checksums by the length of the hex digest
+fn algorithm_of_length(length: usize) -> Option<HashAlgorithm> {
+    let name = match length {
+        32 => "md5",
+        40 => "sha1",
+        56 => "sha224",
+        64 => "sha256",
+        96 => "sha384",
+        128 => "sha512",
+        _ => return None,
+    };
+    name.parse().ok()
+}
+
+/// Check if the value is a hex digest
+fn is_hex(value: &str) -> bool {
+    !value.is_empty() && value.chars().all(|c| c.is_ascii_hexdigit())
+}
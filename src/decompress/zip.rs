@@ -1,4 +1,4 @@
-use std::io::{Cursor, ErrorKind};
+use std::io::Cursor;
 
 use zip::ZipArchive;
 
@@ -8,11 +8,11 @@ pub struct ZipFactory;
 
 impl DecoderFactory for ZipFactory {
     fn supports(file: &[u8]) -> bool {
-        infer::is(&file, "zip")
+        infer::is(file, "zip")
     }
     fn from_bytes(bytes: Vec<u8>) -> std::io::Result<Box<dyn super::Decoder>> {
         let archive = ZipArchive::new(Cursor::new(bytes))
-            .map_err(|e| std::io::Error::new(ErrorKind::Other, e.to_string()))?;
+            .map_err(|e| std::io::Error::other(e.to_string()))?;
         Ok(Box::new(Zip { zip: archive }))
     }
 }
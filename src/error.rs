@@ -0,0 +1,72 @@
+use std::fmt::Display;
+
+use crate::hash::HashMismatch;
+
+/// Error of a file download
+#[derive(Debug, Clone)]
+pub enum FileError {
+    /// The content don't matches an expected hash
+    HashMismatch(Box<HashMismatch>),
+    /// The signature of the content is not valid
+    Signature(String),
+    /// Any other error
+    Other(String),
+}
+impl FileError {
+    /// The download was cancelled by the batch
+    pub(crate) fn is_cancelled(&self) -> bool {
+        matches!(self, Self::Other(err) if err == crate::CANCELLED)
+    }
+}
+impl Display for FileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::HashMismatch(mismatch) => write!(f, "{mismatch}"),
+            Self::Signature(err) => write!(f, "{err}"),
+            Self::Other(err) => write!(f, "{err}"),
+        }
+    }
+}
+impl std::error::Error for FileError {}
+impl From<String> for FileError {
+    fn from(err: String) -> Self {
+        Self::Other(err)
+    }
+}
+
+/// Error of a batch, with the error of every failed file
+#[derive(Debug, Clone)]
+pub struct BatchError {
+    /// Description of the error
+    pub message: String,
+    /// Url and error of the failed files
+    pub failures: Vec<(String, FileError)>,
+}
+impl BatchError {
+    /// Hash mismatches of the failed files
+    pub fn mismatches(&self) -> impl Iterator<Item = &HashMismatch> {
+        self.failures.iter().filter_map(|(_, err)| match err {
+            FileError::HashMismatch(mismatch) => Some(mismatch.as_ref()),
+            FileError::Signature(_) | FileError::Other(_) => None,
+        })
+    }
+}
+impl Display for BatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+impl std::error::Error for BatchError {}
+impl From<String> for BatchError {
+    fn from(message: String) -> Self {
+        Self {
+            message,
+            failures: Vec::new(),
+        }
+    }
+}
+impl From<BatchError> for String {
+    fn from(err: BatchError) -> Self {
+        err.message
+    }
+}
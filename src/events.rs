@@ -0,0 +1,94 @@
+use std::sync::mpsc::Sender;
+
+use crate::{
+    Downloaded,
+    error::FileError,
+    indicator::{Event, Indicator},
+};
+
+/// Stable id of a file, the position of the file in the batch
+pub type FileId = usize;
+
+/// Structured event of a file download
+#[derive(Debug, Clone)]
+pub enum DownloadEvent {
+    /// The file was added to the batch and waits a download slot
+    Queued { id: FileId, url: String },
+    /// The download started
+    Started { id: FileId, name: String },
+    /// The length of the content is known
+    Length { id: FileId, bytes: u64 },
+    /// Bytes downloaded so far
+    Progress { id: FileId, bytes: u64 },
+    /// The download changed of stage, as the extraction
+    Stage { id: FileId, stage: String },
+    /// The download failed and will be retried, the attempts start at 1.
+    /// Not sent yet, the downloader doesn't retry the failed files
+    Retry {
+        id: FileId,
+        attempt: u32,
+        error: String,
+    },
+    /// The download finished, in a transactional batch after the commit
+    Finished {
+        id: FileId,
+        result: Result<Downloaded, FileError>,
+    },
+}
+
+/// Send an event, ignoring a dropped receiver
+pub(crate) fn send(events: &Option<Sender<DownloadEvent>>, event: DownloadEvent) {
+    if let Some(events) = events {
+        let _ = events.send(event);
+    }
+}
+
+/// Indicator that forwards the events to the channel and to the inner indicator
+pub(crate) struct EventIndicator {
+    id: FileId,
+    inner: Box<dyn Indicator + Send>,
+    events: Sender<DownloadEvent>,
+}
+impl EventIndicator {
+    pub fn new(
+        id: FileId,
+        inner: Box<dyn Indicator + Send>,
+        events: Sender<DownloadEvent>,
+    ) -> Self {
+        Self { id, inner, events }
+    }
+}
+impl Indicator for EventIndicator {
+    fn event(&mut self, event: Event) {
+        let id = self.id;
+        let forwarded = match &event {
+            Event::Update(bytes) => Some(DownloadEvent::Progress { id, bytes: *bytes }),
+            Event::Length(bytes) => Some(DownloadEvent::Length { id, bytes: *bytes }),
+            Event::Stage(stage) => Some(DownloadEvent::Stage {
+                id,
+                stage: stage.clone(),
+            }),
+            // The result is sent by the downloader
+            Event::End | Event::Error(_) => None,
+        };
+        if let Some(forwarded) = forwarded {
+            let _ = self.events.send(forwarded);
+        }
+        self.inner.event(event);
+    }
+    fn update(&mut self, bytes: u64) {
+        self.inner.update(bytes);
+    }
+    fn error(&mut self, error: String) {
+        self.inner.error(error);
+    }
+    fn stage(&mut self, stage: String) {
+        self.inner.stage(stage);
+    }
+    fn end(&mut self) {
+        self.inner.end();
+    }
+    fn length(&mut self, bytes: u64) {
+        self.inner.length(bytes);
+    }
+}
@@ -0,0 +1,146 @@
+use std::{any::TypeId, fmt::Display, str::FromStr};
+
+use super::Hasher;
+
+/// Hash algorithm selected at runtime, only the algorithms of the enabled features are available
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HashAlgorithm {
+    #[cfg(feature = "sha")]
+    Sha1,
+    #[cfg(feature = "sha")]
+    Sha224,
+    #[cfg(feature = "sha")]
+    Sha256,
+    #[cfg(feature = "sha")]
+    Sha384,
+    #[cfg(feature = "sha")]
+    Sha512,
+    #[cfg(feature = "sha")]
+    Sha512_224,
+    #[cfg(feature = "sha")]
+    Sha512_256,
+    #[cfg(feature = "md5")]
+    Md5,
+    #[cfg(feature = "sha3")]
+    Sha3_224,
+    #[cfg(feature = "sha3")]
+    Sha3_256,
+    #[cfg(feature = "sha3")]
+    Sha3_384,
+    #[cfg(feature = "sha3")]
+    Sha3_512,
+    #[cfg(feature = "blake2")]
+    Blake2b512,
+    #[cfg(feature = "blake2")]
+    Blake2s256,
+    #[cfg(feature = "blake3")]
+    Blake3,
+    #[cfg(feature = "crc32")]
+    Crc32,
+    #[cfg(feature = "xxhash")]
+    Xxh64,
+    #[cfg(feature = "xxhash")]
+    Xxh3,
+    #[cfg(feature = "xxhash")]
+    Xxh128,
+}
+
+/// Expand the table of the algorithms, `(variant, name, hasher type)`
+macro_rules! algorithms {
+    ($callback:ident) => {
+        $callback! {
+            #[cfg(feature = "sha")] (Sha1, "sha1", sha1::Sha1),
+            #[cfg(feature = "sha")] (Sha224, "sha224", sha2::Sha224),
+            #[cfg(feature = "sha")] (Sha256, "sha256", sha2::Sha256),
+            #[cfg(feature = "sha")] (Sha384, "sha384", sha2::Sha384),
+            #[cfg(feature = "sha")] (Sha512, "sha512", sha2::Sha512),
+            #[cfg(feature = "sha")] (Sha512_224, "sha512-224", sha2::Sha512_224),
+            #[cfg(feature = "sha")] (Sha512_256, "sha512-256", sha2::Sha512_256),
+            #[cfg(feature = "md5")] (Md5, "md5", super::md5::Md5),
+            #[cfg(feature = "sha3")] (Sha3_224, "sha3-224", sha3::Sha3_224),
+            #[cfg(feature = "sha3")] (Sha3_256, "sha3-256", sha3::Sha3_256),
+            #[cfg(feature = "sha3")] (Sha3_384, "sha3-384", sha3::Sha3_384),
+            #[cfg(feature = "sha3")] (Sha3_512, "sha3-512", sha3::Sha3_512),
+            #[cfg(feature = "blake2")] (Blake2b512, "blake2b", blake2::Blake2b512),
+            #[cfg(feature = "blake2")] (Blake2s256, "blake2s", blake2::Blake2s256),
+            #[cfg(feature = "blake3")] (Blake3, "blake3", super::blake3::Blake3),
+            #[cfg(feature = "crc32")] (Crc32, "crc32", super::crc32::Crc32),
+            #[cfg(feature = "xxhash")] (Xxh64, "xxh64", super::xxhash::Xxh64),
+            #[cfg(feature = "xxhash")] (Xxh3, "xxh3", super::xxhash::Xxh3),
+            #[cfg(feature = "xxhash")] (Xxh128, "xxh128", super::xxhash::Xxh128),
+        }
+    };
+}
+
+/// Implement the methods of the algorithms from the table
+macro_rules! impl_algorithms {
+    ($(#[$cfg:meta] ($variant:ident, $name:literal, $ty:ty),)*) => {
+        impl HashAlgorithm {
+            /// Every available algorithm
+            pub const ALL: &[HashAlgorithm] = &[$(#[$cfg] HashAlgorithm::$variant,)*];
+            /// Canonical name of the algorithm, as `sha256` or `sha3-256`
+            pub fn name(&self) -> &'static str {
+                match *self {
+                    $(#[$cfg] HashAlgorithm::$variant => $name,)*
+                }
+            }
+            /// Function that creates a hasher of the algorithm
+            pub fn constructor(&self) -> fn() -> Box<dyn Hasher> {
+                match *self {
+                    $(#[$cfg] HashAlgorithm::$variant => super::boxed::<$ty>,)*
+                }
+            }
+            /// Type of the hasher of the algorithm
+            fn type_id(&self) -> TypeId {
+                match *self {
+                    $(#[$cfg] HashAlgorithm::$variant => TypeId::of::<$ty>(),)*
+                }
+            }
+        }
+    };
+}
+
+algorithms!(impl_algorithms);
+
+impl HashAlgorithm {
+    /// Create a new hasher of the algorithm
+    pub fn hasher(&self) -> Box<dyn Hasher> {
+        (self.constructor())()
+    }
+    /// Algorithm of a hasher type, if is a known algorithm
+    pub fn of<T: Hasher + 'static>() -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|algorithm| algorithm.type_id() == TypeId::of::<T>())
+            .copied()
+    }
+}
+impl Display for HashAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+impl FromStr for HashAlgorithm {
+    type Err = String;
+    /// Parse the name of the algorithm, ignoring the case, `-` and `_` (`SHA-256`, `sha3_256`)
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let normalize = |name: &str| {
+            name.chars()
+                .filter(|c| *c != '-' && *c != '_')
+                .collect::<String>()
+                .to_ascii_lowercase()
+        };
+        let normalized = normalize(name);
+        let normalized = match normalized.as_str() {
+            "blake2b512" => "blake2b".to_string(),
+            "blake2s256" => "blake2s".to_string(),
+            "xxh364" => "xxh3".to_string(),
+            _ => normalized,
+        };
+        Self::ALL
+            .iter()
+            .find(|algorithm| normalize(algorithm.name()) == normalized)
+            .copied()
+            .ok_or(format!("Unknown hash algorithm: {name}"))
+    }
+}
@@ -0,0 +1,3 @@
+use blake2::{Blake2b512, Blake2s256};
+
+impl_digest!(blake2::Digest; Blake2b512, Blake2s256);
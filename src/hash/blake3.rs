@@ -0,0 +1,14 @@
+use super::{Digest, Hasher};
+
+pub struct Blake3(blake3::Hasher);
+impl Hasher for Blake3 {
+    fn new() -> Self {
+        Self(blake3::Hasher::new())
+    }
+    fn update(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+    fn finalize(self: Box<Self>) -> Digest {
+        Digest::new(self.0.finalize().as_bytes().to_vec())
+    }
+}
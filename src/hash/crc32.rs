@@ -0,0 +1,15 @@
+use super::{Digest, Hasher};
+
+/// CRC-32 (IEEE), the digest is big endian as the published checksums
+pub struct Crc32(crc32fast::Hasher);
+impl Hasher for Crc32 {
+    fn new() -> Self {
+        Self(crc32fast::Hasher::new())
+    }
+    fn update(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+    fn finalize(self: Box<Self>) -> Digest {
+        Digest::new(self.0.finalize().to_be_bytes().to_vec())
+    }
+}
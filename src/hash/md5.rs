@@ -1,9 +1,14 @@
-use super::Hasher;
+use super::{Digest, Hasher};
 
-pub struct Md5;
+pub struct Md5(md5::Context);
 impl Hasher for Md5 {
-    fn compute(bytes: &[u8]) -> Result<String, String> {
-        let hash = md5::compute(bytes);
-        Ok(hex::encode(hash.as_slice()))
+    fn new() -> Self {
+        Self(md5::Context::new())
+    }
+    fn update(&mut self, bytes: &[u8]) {
+        self.0.consume(bytes);
+    }
+    fn finalize(self: Box<Self>) -> Digest {
+        Digest::new(self.0.finalize().to_vec())
     }
 }
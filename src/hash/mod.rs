@@ -1,7 +1,43 @@
-use std::{fs, hash::DefaultHasher, path::Path};
+use std::{
+    fmt::Display,
+    fs,
+    hash::DefaultHasher,
+    io::{self, BufReader, Read},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
+use base64::{
+    Engine,
+    engine::general_purpose::{STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD},
+};
 use std::hash::Hasher as RustHasher;
-use tracing::{debug, error, warn};
+use tracing::{debug, warn};
+
+use algorithm::HashAlgorithm;
+
+/// Implement the hasher for the RustCrypto digests
+#[cfg(any(feature = "sha", feature = "sha3", feature = "blake2"))]
+macro_rules! impl_digest {
+    ($digest:path; $($ty:ty),*) => {
+        $(
+            impl $crate::hash::Hasher for $ty {
+                fn new() -> Self {
+                    <$ty as $digest>::new()
+                }
+                fn update(&mut self, bytes: &[u8]) {
+                    <$ty as $digest>::update(self, bytes);
+                }
+                fn finalize(self: Box<Self>) -> $crate::hash::Digest {
+                    $crate::hash::Digest::new(<$ty as $digest>::finalize(*self).to_vec())
+                }
+            }
+        )*
+    };
+}
+
+/// Hash algorithms selected at runtime
+pub mod algorithm;
 
 #[cfg(feature = "sha")]
 pub mod sha;
@@ -9,50 +45,269 @@ pub mod sha;
 #[cfg(feature = "md5")]
 pub mod md5;
 
-/// Common trait for the hashers
-pub trait Hasher {
+#[cfg(feature = "sha3")]
+pub mod sha3;
+
+#[cfg(feature = "blake2")]
+pub mod blake2;
+
+#[cfg(feature = "blake3")]
+pub mod blake3;
+
+#[cfg(feature = "crc32")]
+pub mod crc32;
+
+#[cfg(feature = "xxhash")]
+pub mod xxhash;
+
+/// Deterministic hashes of directory trees
+pub mod tree;
+/// Bulk verification of existing files
+pub mod verify;
+
+/// Size of the buffer used to hash the readers
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Result of a hasher
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Digest(Vec<u8>);
+impl Digest {
+    /// Create a digest from the hash bytes
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+    /// Bytes of the hash
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+    /// Hash encoded in lowercase hex
+    pub fn to_hex(&self) -> String {
+        hex::encode(&self.0)
+    }
+}
+impl Display for Digest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_hex())
+    }
+}
+
+/// Common trait for the hashers, the content is hashed incrementally
+pub trait Hasher: Send {
+    /// Create a new hasher
+    fn new() -> Self
+    where
+        Self: Sized;
+    /// Add bytes to the hash
+    fn update(&mut self, bytes: &[u8]);
+    /// Finish the hash
+    fn finalize(self: Box<Self>) -> Digest;
     /// Calculate a hash from bytes
     fn compute(bytes: &[u8]) -> Result<String, String>
     where
-        Self: Sized;
+        Self: Sized + 'static,
+    {
+        let mut hasher = Box::new(Self::new());
+        hasher.update(bytes);
+        Ok(hasher.finalize().to_hex())
+    }
 }
 
 impl Hasher for std::hash::DefaultHasher {
-    fn compute(bytes: &[u8]) -> Result<String, String> {
-        let mut hasher = DefaultHasher::new();
-        hasher.write(bytes);
-        let hash = hasher.finish();
-        Ok(hex::encode(hash.to_be_bytes()))
+    fn new() -> Self {
+        DefaultHasher::new()
+    }
+    fn update(&mut self, bytes: &[u8]) {
+        self.write(bytes);
     }
+    fn finalize(self: Box<Self>) -> Digest {
+        Digest::new(self.finish().to_be_bytes().to_vec())
+    }
+}
+
+/// Create a boxed hasher
+fn boxed<T: Hasher + 'static>() -> Box<dyn Hasher> {
+    Box::new(T::new())
+}
+
+/// Hash the content of a reader with constant memory
+pub fn digest_reader<R: Read>(hasher: Box<dyn Hasher>, reader: R) -> io::Result<Digest> {
+    let mut digests = digest_reader_all(vec![hasher], reader)?;
+    Ok(digests.remove(0))
+}
+
+/// Hash the content of a reader with several hashers in a single pass
+pub fn digest_reader_all<R: Read>(
+    mut hashers: Vec<Box<dyn Hasher>>,
+    mut reader: R,
+) -> io::Result<Vec<Digest>> {
+    let mut buffer = vec![0u8; BUFFER_SIZE];
+    loop {
+        let size = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(size) => size,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        for hasher in hashers.iter_mut() {
+            hasher.update(&buffer[..size]);
+        }
+    }
+    Ok(hashers
+        .into_iter()
+        .map(|hasher| hasher.finalize())
+        .collect())
+}
+
+/// Content that don't matches an expected hash
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashMismatch {
+    /// Url of the file
+    pub url: String,
+    /// Algorithm of the hash, `None` if the hasher is not a known algorithm
+    pub algorithm: Option<HashAlgorithm>,
+    /// Expected digest, in lowercase hex
+    pub expected: String,
+    /// Computed digest, in lowercase hex
+    pub actual: String,
+    /// Size of the content
+    pub size: u64,
+    /// Path of the content, if was moved to the quarantine directory
+    pub quarantined: Option<PathBuf>,
+}
+impl Display for HashMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let algorithm = self.algorithm.map(|a| a.name()).unwrap_or("hash");
+        write!(
+            f,
+            "Hash mismatch of {} ({} bytes): expected {algorithm} {}, got {}",
+            self.url, self.size, self.expected, self.actual
+        )?;
+        if let Some(path) = &self.quarantined {
+            write!(f, ", quarantined in {}", path.to_string_lossy())?;
+        }
+        Ok(())
+    }
+}
+
+/// Normalize an expected digest in hex or base64 to lowercase hex.
+/// The value is hex if it has the hex length of the digest, when is known
+pub(crate) fn normalize(expect: &str, size: Option<usize>) -> Option<String> {
+    let expect = expect.trim();
+    let hex = expect.len().is_multiple_of(2)
+        && size.is_none_or(|size| expect.len() == size * 2)
+        && expect.chars().all(|c| c.is_ascii_hexdigit());
+    if hex {
+        return Some(expect.to_ascii_lowercase());
+    }
+    [STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD]
+        .iter()
+        .find_map(|engine| engine.decode(expect).ok())
+        .map(hex::encode)
 }
 
 /// Hash check configuration
 #[derive(Debug, Clone)]
 pub struct Hash {
-    /// Expected hash
+    /// Expected hash, in lowercase hex
     expect: String,
-    /// Function that calculates the hash
-    hasher: fn(&[u8]) -> Result<String, String>,
+    /// Function that creates the hasher
+    hasher: fn() -> Box<dyn Hasher>,
+    /// Algorithm of the hasher, if is a known algorithm
+    algorithm: Option<HashAlgorithm>,
 }
 impl Hash {
-    /// Creates a new configuration from the expected hash
+    /// Creates a new configuration from the expected hash, in hex (any case) or base64
     pub fn new<T: Hasher + 'static>(expect: &str) -> Self {
+        let expect = normalize(expect, None).unwrap_or_else(|| {
+            warn!("The expected hash is not hex or base64: {expect}");
+            expect.to_string()
+        });
         Self {
-            expect: expect.to_string(),
-            hasher: T::compute,
+            expect,
+            hasher: boxed::<T>,
+            algorithm: HashAlgorithm::of::<T>(),
+        }
+    }
+    /// Creates a new configuration from an algorithm selected at runtime
+    /// and the expected hash, in hex (any case) or base64
+    pub fn from_algorithm(algorithm: HashAlgorithm, expect: &str) -> Result<Self, String> {
+        let size = algorithm.hasher().finalize().as_bytes().len();
+        let expect = normalize(expect, Some(size))
+            .filter(|expect| expect.len() == size * 2)
+            .ok_or(format!("Invalid {algorithm} digest: {expect}"))?;
+        Ok(Self {
+            expect,
+            hasher: algorithm.constructor(),
+            algorithm: Some(algorithm),
+        })
+    }
+    /// Parse a hash with the algorithm, as `sha256:<hex>`, OCI digests or
+    /// Subresource Integrity strings (`sha384-<base64>`)
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let value = value.trim();
+        if value.contains(char::is_whitespace) {
+            return Err(format!("Expected a single hash: {value}"));
         }
+        let (algorithm, expect) = match value.split_once(':') {
+            Some(parts) => parts,
+            // The options of the integrity strings are ignored
+            None => value
+                .split('?')
+                .next()
+                .and_then(|value| value.split_once('-'))
+                .ok_or(format!("Missing the algorithm of the hash: {value}"))?,
+        };
+        Self::from_algorithm(algorithm.parse()?, expect)
+    }
+    /// Compute the digests of the hashes in a single pass over the reader,
+    /// in the same order of the hashes
+    pub fn digest_all<R: Read>(hashes: &[Hash], reader: R) -> io::Result<Vec<Digest>> {
+        digest_reader_all(hashes.iter().map(Hash::hasher).collect(), reader)
+    }
+    /// Check every digest with the hash in the same position
+    /// Returns None if any don't matches
+    pub fn check_all(hashes: &[Hash], digests: &[Digest]) -> Option<()> {
+        hashes
+            .iter()
+            .zip(digests)
+            .try_for_each(|(hash, digest)| hash.check_digest(digest))
+    }
+    /// Expected digest, in lowercase hex
+    pub fn expected(&self) -> &str {
+        &self.expect
+    }
+    /// Algorithm of the hash, `None` if the hasher is not a known algorithm
+    pub fn algorithm(&self) -> Option<HashAlgorithm> {
+        self.algorithm
+    }
+    /// Create a new hasher of the algorithm
+    pub fn hasher(&self) -> Box<dyn Hasher> {
+        (self.hasher)()
     }
     /// Check if the bytes matches with the expected hash
     /// Returns None if don't matches
     pub fn check_bytes(&self, bytes: Vec<u8>) -> Option<()> {
-        let hash = (self.hasher)(&bytes);
-        let hash = match hash {
-            Result::Err(e) => {
-                error!("Failed computing hash -- {e}");
-                return None;
-            }
-            Result::Ok(hash) => hash,
-        };
+        let mut hasher = self.hasher();
+        hasher.update(&bytes);
+        self.check_digest(&hasher.finalize())
+    }
+    /// Check if the content of the reader matches with the expected hash,
+    /// the content is hashed in chunks
+    /// Returns None if don't matches
+    pub fn check_reader<R: Read>(&self, reader: R) -> io::Result<Option<()>> {
+        let digest = digest_reader(self.hasher(), reader)?;
+        Ok(self.check_digest(&digest))
+    }
+    /// Check if the file matches with the expected hash
+    /// Returns None if don't matches
+    pub fn check_file<P: AsRef<Path>>(&self, file: P) -> io::Result<Option<()>> {
+        let file = fs::File::open(file.as_ref())?;
+        self.check_reader(BufReader::new(file))
+    }
+    /// Check if the digest matches with the expected hash
+    /// Returns None if don't matches
+    pub fn check_digest(&self, digest: &Digest) -> Option<()> {
+        let hash = digest.to_hex();
         debug!("HASH: {}", hash);
         if self.expect.ne(&hash) {
             warn!("Hash don't match");
@@ -60,10 +315,10 @@ impl Hash {
         }
         Some(())
     }
-    /// Check if the file matches with the expected hash
-    /// Returns None if don't matches
-    pub fn check_file<P: AsRef<Path>>(&self, file: P) -> std::io::Result<Option<()>> {
-        let bytes = fs::read(file.as_ref())?;
-        Ok(self.check_bytes(bytes))
+}
+impl FromStr for Hash {
+    type Err = String;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Self::parse(value)
     }
 }
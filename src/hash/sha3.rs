@@ -0,0 +1,3 @@
+use sha3::{Sha3_224, Sha3_256, Sha3_384, Sha3_512};
+
+impl_digest!(sha3::Digest; Sha3_224, Sha3_256, Sha3_384, Sha3_512);
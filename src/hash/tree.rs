@@ -0,0 +1,230 @@
+use std::{
+    fs,
+    io::{self, BufReader},
+    path::Path,
+};
+
+use super::{Digest, Hash, Hasher, digest_reader};
+
+/// Kind of an entry of a directory tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryKind {
+    /// Directory
+    Dir,
+    /// Regular file, with the digest of the content
+    File { executable: bool, digest: Digest },
+    /// Symbolic link, with the target as written in the link
+    Symlink { target: String },
+}
+
+/// Entry of a directory tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeEntry {
+    /// Path relative to the root, separated by `/`
+    pub path: String,
+    /// Kind of the entry
+    pub kind: EntryKind,
+}
+impl TreeEntry {
+    /// Line of the entry in the manifest, `kind\tdata\tpath`
+    fn line(&self) -> String {
+        let (kind, data) = match &self.kind {
+            EntryKind::Dir => ("dir", String::from("-")),
+            EntryKind::File {
+                executable: false,
+                digest,
+            } => ("file", digest.to_hex()),
+            EntryKind::File {
+                executable: true,
+                digest,
+            } => ("exec", digest.to_hex()),
+            EntryKind::Symlink { target } => ("link", escape(target)),
+        };
+        format!("{kind}\t{data}\t{}\n", escape(&self.path))
+    }
+}
+
+/// Change between two trees
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TreeChange {
+    /// The path only exists in the new tree
+    Added(String),
+    /// The path only exists in the old tree
+    Removed(String),
+    /// The kind, the mode, the content or the target of the path changed
+    Modified(String),
+}
+
+/// Deterministic listing of a directory, sorted by path.
+/// Only the executable bit of the modes is kept, the timestamps and the owners are ignored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tree {
+    /// Entries of the tree, sorted by path
+    pub entries: Vec<TreeEntry>,
+}
+impl Tree {
+    /// Walk a directory, hashing the content of the files.
+    /// The symlinks are not followed
+    pub fn compute<P: AsRef<Path>>(dir: P, hasher: fn() -> Box<dyn Hasher>) -> io::Result<Self> {
+        let mut entries = Vec::new();
+        walk(dir.as_ref(), "", hasher, &mut entries)?;
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(Self { entries })
+    }
+    /// Canonical manifest of the tree, a line per entry. The tree digest is the hash of the manifest
+    pub fn manifest(&self) -> String {
+        self.entries.iter().map(TreeEntry::line).collect()
+    }
+    /// Digest of the tree
+    pub fn digest(&self, hasher: fn() -> Box<dyn Hasher>) -> Digest {
+        let mut hasher = hasher();
+        hasher.update(self.manifest().as_bytes());
+        hasher.finalize()
+    }
+    /// Compare with a newer tree, as the same directory later
+    pub fn diff(&self, newer: &Tree) -> Vec<TreeChange> {
+        let mut changes = Vec::new();
+        let (mut old, mut new) = (
+            self.entries.iter().peekable(),
+            newer.entries.iter().peekable(),
+        );
+        loop {
+            match (old.peek(), new.peek()) {
+                (Some(a), Some(b)) if a.path == b.path => {
+                    if a.kind != b.kind {
+                        changes.push(TreeChange::Modified(a.path.clone()));
+                    }
+                    old.next();
+                    new.next();
+                }
+                (Some(a), Some(b)) if a.path < b.path => {
+                    changes.push(TreeChange::Removed(a.path.clone()));
+                    old.next();
+                }
+                (Some(a), None) => {
+                    changes.push(TreeChange::Removed(a.path.clone()));
+                    old.next();
+                }
+                (_, Some(b)) => {
+                    changes.push(TreeChange::Added(b.path.clone()));
+                    new.next();
+                }
+                (None, None) => break,
+            }
+        }
+        changes
+    }
+    /// Parse a manifest written with [Tree::manifest]
+    pub fn parse(manifest: &str) -> Result<Self, String> {
+        let mut entries = Vec::new();
+        for line in manifest.lines().filter(|line| !line.is_empty()) {
+            let invalid = || format!("Invalid tree manifest line: {line}");
+            let mut fields = line.splitn(3, '\t');
+            let (Some(kind), Some(data), Some(path)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                return Err(invalid());
+            };
+            let digest = || hex::decode(data).map(Digest::new).map_err(|_| invalid());
+            let kind = match kind {
+                "dir" => EntryKind::Dir,
+                "file" => EntryKind::File {
+                    executable: false,
+                    digest: digest()?,
+                },
+                "exec" => EntryKind::File {
+                    executable: true,
+                    digest: digest()?,
+                },
+                "link" => EntryKind::Symlink {
+                    target: unescape(data),
+                },
+                _ => return Err(invalid()),
+            };
+            entries.push(TreeEntry {
+                path: unescape(path),
+                kind,
+            });
+        }
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(Self { entries })
+    }
+}
+
+impl Hash {
+    /// Check the tree digest of a directory, see [Tree]
+    /// Returns None if don't matches
+    pub fn check_tree<P: AsRef<Path>>(&self, dir: P) -> io::Result<Option<()>> {
+        let tree = Tree::compute(dir, self.hasher)?;
+        Ok(self.check_digest(&tree.digest(self.hasher)))
+    }
+}
+
+/// Add the entries of a directory recursively
+fn walk(
+    dir: &Path,
+    prefix: &str,
+    hasher: fn() -> Box<dyn Hasher>,
+    entries: &mut Vec<TreeEntry>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let path = format!("{prefix}{name}");
+        let file_type = entry.file_type()?;
+        let kind = if file_type.is_symlink() {
+            let target = fs::read_link(entry.path())?;
+            EntryKind::Symlink {
+                target: target.to_string_lossy().replace('\\', "/"),
+            }
+        } else if file_type.is_dir() {
+            walk(&entry.path(), &format!("{path}/"), hasher, entries)?;
+            EntryKind::Dir
+        } else {
+            let file = fs::File::open(entry.path())?;
+            EntryKind::File {
+                executable: is_executable(&entry.metadata()?),
+                digest: digest_reader(hasher(), BufReader::new(file))?,
+            }
+        };
+        entries.push(TreeEntry { path, kind });
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+/// Escape the separators of the manifest
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+}
+
+/// Revert [escape]
+fn unescape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('t') => result.push('\t'),
+                Some('n') => result.push('\n'),
+                Some(other) => result.push(other),
+                None => result.push('\\'),
+            },
+            c => result.push(c),
+        }
+    }
+    result
+}
@@ -0,0 +1,246 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+    time::Instant,
+};
+
+use tracing::{debug, warn};
+
+use super::{BUFFER_SIZE, Digest, Hash, Hasher};
+use crate::{
+    PROGRESS_BYTES, PROGRESS_INTERVAL,
+    checksum::ChecksumEntry,
+    indicator::{Event, Indicator, IndicatorFactory},
+};
+
+/// Default size from which the files are memory-mapped, 16MB
+pub const MMAP_THRESHOLD: u64 = 16 * 1024 * 1024;
+
+/// Verdict of the verification of a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// Every hash matches
+    Passed,
+    /// A hash don't matches
+    Failed { expected: String, actual: String },
+    /// The file doesn't exist
+    Missing,
+    /// The file couldn't be read
+    Error(String),
+}
+
+/// Result of the verification of a file
+#[derive(Debug, Clone)]
+pub struct Verified {
+    /// Path of the file
+    pub path: PathBuf,
+    /// Verdict of the file
+    pub verdict: Verdict,
+    /// Computed digests, in the order of the hashes of the file
+    pub digests: Vec<Digest>,
+}
+impl Verified {
+    /// Check if every hash of the file matches
+    pub fn passed(&self) -> bool {
+        self.verdict == Verdict::Passed
+    }
+}
+
+/// Bulk verification of existing files, hashed concurrently by a pool of threads
+pub struct Verifier {
+    indicator: Box<dyn IndicatorFactory + Send + Sync>,
+    files: Vec<(PathBuf, Vec<Hash>)>,
+    /// Position of each path in the files
+    index: HashMap<PathBuf, usize>,
+    threads: usize,
+    mmap_threshold: u64,
+}
+impl Verifier {
+    pub fn new<T: IndicatorFactory + Sync + Send + 'static>(indicator: T) -> Self {
+        Self {
+            indicator: Box::new(indicator),
+            files: Vec::new(),
+            index: HashMap::new(),
+            threads: thread::available_parallelism().map_or(4, |n| n.get()),
+            mmap_threshold: MMAP_THRESHOLD,
+        }
+    }
+    /// Add a file with the expected hash, the hashes of the same path are checked in a single pass
+    pub fn with_file<P: AsRef<Path>>(mut self, path: P, hash: Hash) -> Self {
+        let path = path.as_ref().to_path_buf();
+        match self.index.get(&path) {
+            Some(&index) => self.files[index].1.push(hash),
+            None => {
+                self.index.insert(path.clone(), self.files.len());
+                self.files.push((path, vec![hash]));
+            }
+        }
+        self
+    }
+    /// Add the files of a checksum file, relative to a directory
+    pub fn with_checksums<P: AsRef<Path>>(
+        mut self,
+        dir: P,
+        entries: &[ChecksumEntry],
+    ) -> Result<Self, String> {
+        for entry in entries {
+            self = self.with_file(dir.as_ref().join(&entry.name), entry.hash()?);
+        }
+        Ok(self)
+    }
+    /// Sets the number of threads of the pool, default the available parallelism
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+    /// Sets the size from which the files are memory-mapped, default [MMAP_THRESHOLD].
+    /// Requires the `mmap` feature, otherwise the files are always read in chunks
+    pub fn with_mmap_threshold(mut self, bytes: u64) -> Self {
+        self.mmap_threshold = bytes;
+        self
+    }
+    /// Verify every file, the results are in the order of the files.
+    /// The files must not be written during the verification, a memory-mapped file
+    /// truncated while is hashed kills the process
+    pub fn start(self) -> Vec<Verified> {
+        let next = AtomicUsize::new(0);
+        let factory = Mutex::new(self.indicator);
+        let results = Mutex::new(Vec::with_capacity(self.files.len()));
+        let files = &self.files;
+        thread::scope(|scope| {
+            for _ in 0..self.threads.min(files.len()) {
+                scope.spawn(|| {
+                    loop {
+                        let index = next.fetch_add(1, Ordering::SeqCst);
+                        let Some((path, hashes)) = files.get(index) else {
+                            break;
+                        };
+                        let verified = verify(path, hashes, &factory, self.mmap_threshold);
+                        results.lock().unwrap().push((index, verified));
+                    }
+                });
+            }
+        });
+        let mut results = results.into_inner().unwrap();
+        results.sort_by_key(|(index, _)| *index);
+        results.into_iter().map(|(_, verified)| verified).collect()
+    }
+}
+
+/// Verify a file with its indicator
+fn verify(
+    path: &Path,
+    hashes: &[Hash],
+    factory: &Mutex<Box<dyn IndicatorFactory + Send + Sync>>,
+    mmap_threshold: u64,
+) -> Verified {
+    let verified = |verdict, digests| Verified {
+        path: path.to_path_buf(),
+        verdict,
+        digests,
+    };
+    let size = match fs::metadata(path) {
+        Ok(metadata) => metadata.len(),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            warn!("Missing file {}", path.to_string_lossy());
+            return verified(Verdict::Missing, Vec::new());
+        }
+        Err(err) => return verified(Verdict::Error(err.to_string()), Vec::new()),
+    };
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    debug!("Verifying {} of {size} bytes", path.to_string_lossy());
+    let mut indicator = factory.lock().unwrap().create(name, size as usize);
+    let hashers = hashes.iter().map(Hash::hasher).collect();
+    let digests = match digest_path(
+        path,
+        hashers,
+        size > 0 && size >= mmap_threshold,
+        &mut indicator,
+    ) {
+        Ok(digests) => digests,
+        Err(err) => {
+            indicator.event(Event::Error(err.to_string()));
+            return verified(Verdict::Error(err.to_string()), Vec::new());
+        }
+    };
+    let mismatch = hashes
+        .iter()
+        .zip(&digests)
+        .find(|(hash, digest)| hash.check_digest(digest).is_none());
+    let verdict = match mismatch {
+        Some((hash, digest)) => {
+            let (expected, actual) = (hash.expected().to_string(), digest.to_hex());
+            warn!(
+                "Hash mismatch of {}: expected {expected}, got {actual}",
+                path.to_string_lossy()
+            );
+            indicator.event(Event::Error(format!("Hash mismatch, got {actual}")));
+            Verdict::Failed { expected, actual }
+        }
+        None => {
+            indicator.event(Event::End);
+            Verdict::Passed
+        }
+    };
+    verified(verdict, digests)
+}
+
+/// Hash a file in chunks, reporting the progress
+#[allow(unused_variables)]
+fn digest_path(
+    path: &Path,
+    mut hashers: Vec<Box<dyn Hasher>>,
+    mmap: bool,
+    indicator: &mut Box<dyn Indicator + Send>,
+) -> io::Result<Vec<Digest>> {
+    let mut file = fs::File::open(path)?;
+    let mut position = 0;
+    #[cfg(feature = "mmap")]
+    if mmap {
+        // SAFETY: the map is only read. A file truncated while is mapped raises SIGBUS
+        // when the removed pages are read, the callers must not verify files being written
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        for chunk in map.chunks(PROGRESS_BYTES as usize) {
+            for hasher in hashers.iter_mut() {
+                hasher.update(chunk);
+            }
+            position += chunk.len() as u64;
+            indicator.event(Event::Update(position));
+        }
+        return Ok(hashers.into_iter().map(|h| h.finalize()).collect());
+    }
+    // Position and time of the last progress update
+    let mut reported = (0u64, Instant::now());
+    let mut buffer = vec![0u8; BUFFER_SIZE];
+    loop {
+        let size = match file.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(size) => size,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        for hasher in hashers.iter_mut() {
+            hasher.update(&buffer[..size]);
+        }
+        position += size as u64;
+        // Coalesce the chunks by bytes or time
+        if position - reported.0 >= PROGRESS_BYTES || reported.1.elapsed() >= PROGRESS_INTERVAL {
+            indicator.event(Event::Update(position));
+            reported = (position, Instant::now());
+        }
+    }
+    if reported.0 != position {
+        indicator.event(Event::Update(position));
+    }
+    Ok(hashers.into_iter().map(|h| h.finalize()).collect())
+}
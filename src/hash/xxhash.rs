@@ -0,0 +1,45 @@
+use xxhash_rust::{xxh3, xxh64};
+
+use super::{Digest, Hasher};
+
+/// XXH64 with seed 0, the digest is big endian as the `xxhsum` output
+pub struct Xxh64(xxh64::Xxh64);
+impl Hasher for Xxh64 {
+    fn new() -> Self {
+        Self(xxh64::Xxh64::new(0))
+    }
+    fn update(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+    fn finalize(self: Box<Self>) -> Digest {
+        Digest::new(self.0.digest().to_be_bytes().to_vec())
+    }
+}
+
+/// XXH3 64 bits, the digest is big endian as the `xxhsum` output
+pub struct Xxh3(Box<xxh3::Xxh3>);
+impl Hasher for Xxh3 {
+    fn new() -> Self {
+        Self(Box::new(xxh3::Xxh3::new()))
+    }
+    fn update(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+    fn finalize(self: Box<Self>) -> Digest {
+        Digest::new(self.0.digest().to_be_bytes().to_vec())
+    }
+}
+
+/// XXH3 128 bits, the digest is big endian as the `xxhsum` output
+pub struct Xxh128(Box<xxh3::Xxh3>);
+impl Hasher for Xxh128 {
+    fn new() -> Self {
+        Self(Box::new(xxh3::Xxh3::new()))
+    }
+    fn update(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+    fn finalize(self: Box<Self>) -> Digest {
+        Digest::new(self.0.digest128().to_be_bytes().to_vec())
+    }
+}
@@ -61,4 +61,12 @@ impl Indicator for SimpleIndicator {
     fn update(&mut self, bytes: u64) {
         self.bar.set_position(bytes);
     }
+    fn length(&mut self, bytes: u64) {
+        self.bar.set_length(bytes);
+    }
+}
+impl Default for IndicatifFactory {
+    fn default() -> Self {
+        Self::new()
+    }
 }
@@ -33,3 +33,8 @@ impl LogFactory {
         Self {}
     }
 }
+impl Default for LogFactory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -21,6 +21,8 @@ pub enum Event {
     Error(String),
     /// Change the stage of the download
     Stage(String),
+    /// The length of the content is known
+    Length(u64),
 }
 
 /// Factory that creates an indicator
@@ -39,6 +41,7 @@ pub trait Indicator {
             Event::End => self.end(),
             Event::Error(error) => self.error(error),
             Event::Stage(stage) => self.stage(stage),
+            Event::Length(bytes) => self.length(bytes),
         }
     }
     /// Process the update event
@@ -49,4 +52,6 @@ pub trait Indicator {
     fn stage(&mut self, stage: String);
     /// Process the end event
     fn end(&mut self);
+    /// Process the length event, sent when the response declares the length
+    fn length(&mut self, _bytes: u64) {}
 }
@@ -18,3 +18,8 @@ impl SilentFactory {
         Self {}
     }
 }
+impl Default for SilentFactory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
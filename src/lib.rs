@@ -1,42 +1,98 @@
 use std::{
+    collections::{HashMap, HashSet},
     fmt::Debug,
     fs,
-    io::{BufWriter, Write},
+    io::{BufReader, BufWriter, Read, Write},
     path::{Path, PathBuf},
-    sync::{Arc, Mutex},
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, Sender},
+    },
     thread,
+    time::{Duration, Instant},
 };
 
-use tempfile::{NamedTempFile, tempdir};
-use tracing::error;
+use tempfile::{NamedTempFile, TempPath, tempdir};
+use tracing::{debug, error, warn};
 
-use crate::{decompress::DecoderFactory, indicator::IndicatorFactory, utils::Semaphore};
+use crate::{
+    cache::HttpCache,
+    cas::LinkMode,
+    checksum::ChecksumEntry,
+    decompress::DecoderFactory,
+    error::{BatchError, FileError},
+    events::{DownloadEvent, EventIndicator},
+    hash::{Digest, Hash, HashMismatch, algorithm::HashAlgorithm},
+    indicator::IndicatorFactory,
+    redirect::{RedirectPolicy, Routed},
+    signature::{PublicKey, Signature, SignatureSource},
+    sink::Sink,
+    transaction::Staging,
+    utils::Semaphore,
+};
 
+/// Http response cache, conditional requests with etag and last-modified
+pub mod cache;
 /// Content Addreseable Storage utilities and stores
 pub mod cas;
+/// Checksum files, as `SHA256SUMS`, published next to the assets
+pub mod checksum;
 /// Decompression utils, tar zip gz and xz support
 pub mod decompress;
+/// Structured errors of the downloads
+pub mod error;
+/// Channel of structured download events
+pub mod events;
 /// Hashing utilities, support sha*, md5 and default rust hasher
 pub mod hash;
 /// Indicators utilities, and default implementations for indicatif and tracing
 pub mod indicator;
+pub(crate) mod naming;
+/// Dry-run planning of a batch
+pub mod plan;
+/// Redirect policies, max redirects, https only and allowed hosts
+pub mod redirect;
+/// Detached signatures, minisign, signify and ed25519
+pub mod signature;
+/// Destinations for the content of files without path, memory, writers and channels
+pub mod sink;
+/// Disk space preflight check
+pub mod space;
+/// Transactional batches, staging and commit of the outputs
+pub(crate) mod transaction;
 pub(crate) mod utils;
 
 #[cfg(test)]
 mod tests;
 
-/// Define the size of the download buffer, default 1KB
-pub const CHUNK_SIZE: usize = 1024;
+/// Default size of the download buffer, 64KB
+pub const CHUNK_SIZE: usize = 64 * 1024;
+/// Default minimum time between two progress updates
+pub const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+/// Default downloaded bytes that force a progress update, 1MB
+pub const PROGRESS_BYTES: u64 = 1024 * 1024;
+
+/// Computed digests paired with the algorithm of the hashes
+type Digests = Vec<(Option<HashAlgorithm>, Digest)>;
+
+/// Entries of a checksum file, or the error fetching it
+type Checksums = Arc<OnceLock<Result<Vec<ChecksumEntry>, String>>>;
+
+/// Function that creates a decoder from bytes
+type DecoderFn = fn(Vec<u8>) -> Result<Box<dyn crate::decompress::Decoder>, std::io::Error>;
 
 /// Configuration for decompression function
 #[derive(Debug, Clone)]
 pub struct Decompression {
     /// Decoder function than create a decompressor
-    decoder: fn(Vec<u8>) -> Result<Box<dyn crate::decompress::Decoder>, std::io::Error>,
+    decoder: DecoderFn,
     /// Decompression destination path
     dst: PathBuf,
     /// Exclude files or folders here
     exclude: Vec<String>,
+    /// Expected tree digest of the destination after the extraction
+    tree: Option<Hash>,
 }
 impl Decompression {
     /// Create a new configuration
@@ -45,6 +101,7 @@ impl Decompression {
             decoder: T::from_bytes,
             dst: PathBuf::new(),
             exclude: Vec::new(),
+            tree: None,
         }
     }
     /// Sets the destination path
@@ -62,13 +119,37 @@ impl Decompression {
         self.exclude = strs.iter().map(|e| e.to_string()).collect();
         self
     }
+    /// Sets the expected tree digest of the extracted files, without the excluded ones,
+    /// see [hash::tree::Tree]. The destination is only written if the digest matches
+    pub fn with_tree_hash(mut self, hash: Hash) -> Self {
+        self.tree = Some(hash);
+        self
+    }
     /// Function to extract a file with bytes
     pub fn extract(self, bytes: Vec<u8>) -> Result<(), String> {
         let dir = tempdir().map_err(|e| e.to_string())?;
         let mut decoder = (self.decoder)(bytes).map_err(|e| e.to_string())?;
         decoder.extract(dir.path().to_path_buf())?;
-        utils::rcopy(dir, self.dst, self.exclude).map_err(|e| e.to_string())?;
-        Ok(())
+        let (src, exclude) = match &self.tree {
+            Some(hash) => {
+                // The excluded files are removed before hashing the extraction
+                let verified = tempdir().map_err(|e| e.to_string())?;
+                utils::rcopy(&dir, &verified, self.exclude).map_err(|e| e.to_string())?;
+                if hash
+                    .check_tree(&verified)
+                    .map_err(|e| e.to_string())?
+                    .is_none()
+                {
+                    return Err(format!(
+                        "Tree hash of the extraction to {} don't match",
+                        self.dst.to_string_lossy()
+                    ));
+                }
+                (verified, Vec::new())
+            }
+            None => (dir, self.exclude),
+        };
+        utils::rcopy(src, &self.dst, exclude).map_err(|e| e.to_string())
     }
     /// Function to extract a file with path
     pub fn extract_file<P: AsRef<Path>>(self, file: P) -> Result<(), String> {
@@ -87,11 +168,29 @@ pub struct File {
     /// Size of the file
     pub size: u64,
     /// Hash of the file
-    hash: Option<crate::hash::Hash>,
+    hashes: Vec<crate::hash::Hash>,
     /// CAS store
     store: Option<Box<Arc<dyn crate::cas::Store + 'static>>>,
     /// Decompression config
     decompression: Option<Decompression>,
+    /// Http response cache
+    cache: Option<Arc<HttpCache>>,
+    /// Destination of the content when the file don't has path
+    sink: Option<Sink>,
+    /// Directory where the file is downloaded with the name of the response
+    dir: Option<PathBuf>,
+    /// Size of the download buffer, overrides the size of the downloader
+    buffer_size: Option<usize>,
+    /// Url of a checksum file with the hash of the file
+    checksum_url: Option<String>,
+    /// Detached signature of the file
+    signature: Option<Signature>,
+    /// How the store object is materialized, default the mode of the store
+    link_mode: Option<LinkMode>,
+    /// Final path of a file staged by a transaction
+    staged_from: Option<PathBuf>,
+    /// Staging directory of the file names resolved in the directory
+    staged_dir: Option<PathBuf>,
 }
 impl File {
     /// Create a new file
@@ -100,9 +199,18 @@ impl File {
             url: url.to_string(),
             path: PathBuf::new(),
             size: 0,
-            hash: None,
+            hashes: Vec::new(),
             store: None,
             decompression: None,
+            cache: None,
+            sink: None,
+            dir: None,
+            buffer_size: None,
+            checksum_url: None,
+            signature: None,
+            link_mode: None,
+            staged_from: None,
+            staged_dir: None,
         }
     }
     /// Sets the file path
@@ -110,14 +218,26 @@ impl File {
         self.path = path.as_ref().to_path_buf();
         self
     }
+    /// Sets the directory of the file, the file name is inferred from
+    /// the `Content-Disposition` header, the final redirected url or the url
+    pub fn with_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
     /// Sets the file size
     pub fn with_size(mut self, size: u64) -> Self {
         self.size = size;
         self
     }
-    /// Sets the file hash
+    /// Add an expected hash of the file, every hash is checked
+    /// in a single pass and the download fails if any don't matches
     pub fn with_hash(mut self, hash: crate::hash::Hash) -> Self {
-        self.hash = Some(hash);
+        self.hashes.push(hash);
+        self
+    }
+    /// Add several expected hashes of the file
+    pub fn with_hashes(mut self, hashes: Vec<crate::hash::Hash>) -> Self {
+        self.hashes.extend(hashes);
         self
     }
     /// Sets the cas store
@@ -125,44 +245,291 @@ impl File {
         self.store = Some(Box::new(store));
         self
     }
+    /// Sets how the store object is materialized at the path, overriding the mode of the store
+    pub fn with_link_mode(mut self, mode: LinkMode) -> Self {
+        self.link_mode = Some(mode);
+        self
+    }
     /// Sets the decompression config
     pub fn with_decompression(mut self, decompression: Decompression) -> Self {
         self.decompression = Some(decompression);
         self
     }
+    /// Sets the http response cache, the request will be conditional
+    /// and a `304 Not Modified` reuses the cached content
+    pub fn with_cache(mut self, cache: Arc<HttpCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+    /// Sets the sink of the content, used instead of the path
+    pub fn with_sink(mut self, sink: Sink) -> Self {
+        self.sink = Some(sink);
+        self
+    }
+    /// Sets the size of the download buffer, overrides the size of the downloader
+    pub fn with_buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = Some(buffer_size);
+        self
+    }
+    /// Sets the url of a checksum file (`SHA256SUMS`, `asset.sha256`), the entry
+    /// of the file name is added as a hash before the verification
+    pub fn with_checksum_url(mut self, url: &str) -> Self {
+        self.checksum_url = Some(url.to_string());
+        self
+    }
+    /// Sets the detached signature of the file, the url or the content, and the key
+    /// of the signer. Is verified after the hashes, before the store and the decompression
+    pub fn with_signature<S: Into<SignatureSource>>(
+        mut self,
+        signature: S,
+        key: PublicKey,
+    ) -> Self {
+        self.signature = Some(Signature {
+            source: signature.into(),
+            key,
+        });
+        self
+    }
     pub(crate) fn download(
-        self,
-        agent: Arc<ureq::Agent>,
+        mut self,
+        context: &Context,
         indicator: &mut Box<dyn crate::indicator::Indicator + Send>,
-    ) -> Result<(), String> {
-        if self.path.eq(&PathBuf::new()) {
+    ) -> Result<Downloaded, FileError> {
+        if self.sink.is_none() && self.dir.is_none() && self.path.eq(&PathBuf::new()) {
             error!("Please, define the path in the file: {}", self.url);
-            return Err("Undefined Path".to_string());
+            return Err(FileError::Other("Undefined Path".to_string()));
+        }
+        // The streamed content isn't kept, only the hashes can be checked
+        if let Some(sink) = &self.sink
+            && sink.streams()
+            && (self.store.is_some()
+                || self.decompression.is_some()
+                || self.signature.is_some()
+                || self.cache.is_some())
+        {
+            return Err(FileError::Other(format!(
+                "The {sink:?} sink of {} can't be stored, extracted, signed or cached",
+                self.url
+            )));
+        }
+        self.resolve_checksum(|url| context.checksums(url))?;
+        // The file was already downloaded, only extract it
+        if let Some(digests) = self.current_digests() {
+            debug!("Skipping {}, the file matches the hash", self.url);
+            let path = self.path.clone();
+            let mut downloaded = self.finish(context, Content::Current(path), indicator)?;
+            downloaded.digests = digests;
+            return Ok(downloaded);
+        }
+        // The content is in the store, only link it
+        if let Some((object, digests)) = self.stored_object() {
+            debug!("Skipping {}, the content is in the store", self.url);
+            if let Some(store) = &self.store
+                && !self.path.as_os_str().is_empty()
+            {
+                let mode = self.link_mode.unwrap_or(store.link_mode());
+                cas::link(&object, &self.path, mode)?;
+                store.register_root(self.root())?;
+            }
+            let mut downloaded =
+                self.finish(context, Content::Current(object.clone()), indicator)?;
+            if downloaded.path.as_os_str().is_empty() {
+                downloaded.path = object;
+            }
+            downloaded.digests = digests;
+            downloaded.from_store = true;
+            return Ok(downloaded);
+        }
+        let fetched = self.fetch(context, indicator)?;
+        let mut downloaded = self.finish(context, fetched.content, indicator)?;
+        downloaded.final_url = fetched.final_url;
+        downloaded.redirects = fetched.redirects;
+        Ok(downloaded)
+    }
+    /// Add the hash of the entry of the checksum file
+    pub(crate) fn resolve_checksum(
+        &mut self,
+        mut checksums: impl FnMut(&str) -> Result<Vec<ChecksumEntry>, String>,
+    ) -> Result<(), String> {
+        let Some(url) = self.checksum_url.take() else {
+            return Ok(());
+        };
+        let entries = checksums(&url)?;
+        let names: Vec<String> = [
+            self.path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string()),
+            naming::url_filename(&self.url),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        let entry = checksum::find(&entries, &names).ok_or(format!(
+            "No checksum of {} in {url}",
+            names.first().unwrap_or(&self.url)
+        ))?;
+        debug!("Checksum of {}: {}", self.url, entry.digest);
+        self.hashes.push(entry.hash()?);
+        Ok(())
+    }
+    /// Request the file and write the content in the output or the sink
+    fn fetch(
+        &mut self,
+        context: &Context,
+        indicator: &mut Box<dyn crate::indicator::Indicator + Send>,
+    ) -> Result<Fetched, String> {
+        // Search the validators of the previous response
+        let cached = self.cache.as_ref().and_then(|cache| cache.get(&self.url));
+        let mut headers = Vec::new();
+        if let Some(entry) = &cached {
+            if let Some(etag) = &entry.etag {
+                headers.push(("If-None-Match", etag.as_str()));
+            }
+            if let Some(last_modified) = &entry.last_modified {
+                headers.push(("If-Modified-Since", last_modified.as_str()));
+            }
+        }
+        // Use the agent to make a get request to the file url, following the allowed redirects
+        let Routed {
+            response: request,
+            redirects,
+        } = redirect::call(
+            &context.agent,
+            "GET",
+            &self.url,
+            &headers,
+            &context.redirect,
+        )?;
+        let final_url = request.get_url().to_string();
+        // Infer the file name from the response
+        if let Some(dir) = &self.dir {
+            let name = naming::resolve(
+                request.header("Content-Disposition"),
+                request.get_url(),
+                &self.url,
+            );
+            // The name is claimed in the final directory, even if the file is staged
+            let path = context.claim(dir.join(name))?;
+            self.path = match &self.staged_dir {
+                Some(staged) => {
+                    let staged = staged.join(path.strip_prefix(dir).unwrap_or(&path));
+                    self.staged_from = Some(path);
+                    staged
+                }
+                None => path,
+            };
+            debug!("Resolved {} as {}", self.url, self.path.to_string_lossy());
+        }
+        // The content don't changed, reuse the cached content
+        if let (304, Some(entry)) = (request.status(), &cached) {
+            debug!("Not modified, using cached content of {}", self.url);
+            let reader = std::fs::File::open(&entry.body).map_err(|e| e.to_string())?;
+            let content = self.receive(context, Box::new(reader), indicator)?;
+            return Ok(Fetched {
+                content,
+                final_url,
+                redirects,
+            });
         }
-
-        // Use the agent to make a get request to the file url
-        let request = agent.get(&self.url).call().map_err(|e| e.to_string())?;
         // If the response is not ok, comunicateit in the indicator
         if request.status() != 200 {
             let error = request.status_text().to_string();
             indicator.event(indicator::Event::Error(error.clone()));
             return Err(format!("HTTP ERROR: {}", error));
         }
+        // Keep the validators to save them in the cache
+        let etag = request.header("ETag").map(|e| e.to_string());
+        let last_modified = request.header("Last-Modified").map(|e| e.to_string());
+        if let Some(length) = request
+            .header("Content-Length")
+            .and_then(|length| length.parse().ok())
+        {
+            indicator.event(indicator::Event::Length(length));
+        }
+        let content = self.receive(context, request.into_reader(), indicator)?;
+
+        // save the response in the cache
+        if let Some(cache) = &self.cache {
+            let saved = content.reader().and_then(|mut reader| {
+                cache.put(
+                    &self.url,
+                    etag.as_deref(),
+                    last_modified.as_deref(),
+                    &mut reader,
+                )
+            });
+            if let Err(err) = saved {
+                content.discard();
+                return Err(err);
+            }
+        }
+        Ok(Fetched {
+            content,
+            final_url,
+            redirects,
+        })
+    }
+    /// Write the content of the reader in the file path, the temporary file of
+    /// the store or the sink, the partial output is removed if the download fails
+    fn receive(
+        &self,
+        context: &Context,
+        reader: Box<dyn Read + Send>,
+        indicator: &mut Box<dyn crate::indicator::Indicator + Send>,
+    ) -> Result<Content, String> {
+        if let Some(sink) = &self.sink {
+            let (kept, digests) = self.copy(context, reader, sink.open(), indicator)?;
+            return Ok(match kept {
+                Some(kept) => Content::Bytes(kept),
+                None => Content::Streamed(digests),
+            });
+        }
+        if let Some(temp) = self.temp()? {
+            // The temporary file is removed when its path is dropped
+            let (file, temp) = temp.into_parts();
+            self.copy(context, reader, Box::new(BufWriter::new(file)), indicator)?;
+            return Ok(Content::Temp(temp));
+        }
+        let output = &self.path;
+        if !output.parent().unwrap_or(output).exists() {
+            fs::create_dir_all(output.parent().unwrap_or(output)).map_err(|e| e.to_string())?;
+        }
+        let file = std::fs::File::create(output).map_err(|e| e.to_string())?;
+        if let Err(err) = self.copy(context, reader, Box::new(BufWriter::new(file)), indicator) {
+            remove_partial(output);
+            return Err(err);
+        }
+        Ok(Content::Path(output.clone()))
+    }
+    /// Copy the reader in the writer, returns the content of the memory sink
+    /// and the digests of the streamed content
+    fn copy(
+        &self,
+        context: &Context,
+        mut reader: Box<dyn Read + Send>,
+        mut writer: Box<dyn Write + Send>,
+        indicator: &mut Box<dyn crate::indicator::Indicator + Send>,
+    ) -> Result<(Option<Vec<u8>>, Vec<Digest>), String> {
         // Current downloaded bytes
         let mut current = 0u64;
-        // Calculate the file path (if is a the file has a store, return temp file)
-        let path = self.path();
-        // Create the reader from the request and the writer in the file path
-        let mut reader = request.into_reader();
-        if !path.parent().unwrap_or(&path.clone()).exists() {
-            fs::create_dir_all(&path.parent().unwrap_or(&path.clone())).map_err(|e| e.to_string())?;
-        }
-        let mut writer = BufWriter::new(std::fs::File::create(&path).map_err(|e| e.to_string())?);
+        // Only the memory sink keeps the content, the streamed content is hashed on the fly
+        let keep = matches!(self.sink, Some(Sink::Memory));
+        let mut kept = Vec::new();
+        let mut hashers: Vec<Box<dyn hash::Hasher>> = match &self.sink {
+            Some(sink) if sink.streams() => self.hashes.iter().map(Hash::hasher).collect(),
+            _ => Vec::new(),
+        };
         // If the file does't has size, use the maximum to download all the request
         let size = if self.size == 0 { u64::MAX } else { self.size };
+        // The buffer is reused by all the reads
+        let mut buffer = vec![0u8; self.buffer_size.unwrap_or(context.buffer_size).max(1)];
+        // Position and time of the last progress update
+        let mut reported = (0u64, Instant::now());
         while current < size {
-            // Create the chunk buffer
-            let mut buffer = [0u8; CHUNK_SIZE];
+            // Stop if the batch was cancelled
+            if context.cancelled() {
+                return Err(CANCELLED.to_string());
+            }
             // Read a chunk of the request in the buffer
             let size = reader.read(&mut buffer).map_err(|e| e.to_string())?;
             // If the reader does't has read nothing, the download finishes
@@ -174,48 +541,509 @@ impl File {
             // Get the writen buffer data
             let buffer = &buffer[0..size];
             // Write the data in the file
-            writer.write(buffer).map_err(|e| e.to_string())?;
-            // Update the indicator
+            writer.write_all(buffer).map_err(|e| e.to_string())?;
+            if keep {
+                kept.extend_from_slice(buffer);
+            }
+            for hasher in hashers.iter_mut() {
+                hasher.update(buffer);
+            }
+            // Update the indicator, coalescing the chunks by bytes or time
+            if current - reported.0 >= context.progress_bytes
+                || reported.1.elapsed() >= context.progress_interval
+            {
+                indicator.event(indicator::Event::Update(current));
+                reported = (current, Instant::now());
+            }
+        }
+        if reported.0 != current {
             indicator.event(indicator::Event::Update(current));
         }
         // Flush the writer to make sure that the data was entered correctly
         writer.flush().map_err(|e| e.to_string())?;
 
-        // check the file hash
-        if let Some(hash) = self.hash {
-            let check = hash.check_file(&path).map_err(|e| e.to_string())?;
-            if matches!(check, None) {
-                return Err("Hashes don't matches".to_string());
+        let digests = hashers.into_iter().map(|h| h.finalize()).collect();
+        Ok((keep.then_some(kept), digests))
+    }
+    /// Check, store and decompress the downloaded content,
+    /// the outputs of this download are removed if a step fails
+    fn finish(
+        self,
+        context: &Context,
+        content: Content,
+        indicator: &mut Box<dyn crate::indicator::Indicator + Send>,
+    ) -> Result<Downloaded, FileError> {
+        // check the file hashes in a single pass
+        let mut digests = Vec::new();
+        if !self.hashes.is_empty() {
+            let computed = match &content {
+                Content::Path(path) => fs::File::open(path)
+                    .and_then(|file| Hash::digest_all(&self.hashes, BufReader::new(file))),
+                Content::Temp(temp) => fs::File::open(temp)
+                    .and_then(|file| Hash::digest_all(&self.hashes, BufReader::new(file))),
+                Content::Bytes(bytes) => Hash::digest_all(&self.hashes, bytes.as_slice()),
+                Content::Streamed(digests) => Ok(digests.clone()),
+                Content::Current(_) => Ok(Vec::new()),
+            };
+            let computed = match computed {
+                Ok(computed) => computed,
+                Err(err) => {
+                    content.discard();
+                    return Err(FileError::Other(err.to_string()));
+                }
+            };
+            let mismatch = self
+                .hashes
+                .iter()
+                .zip(&computed)
+                .find(|(hash, digest)| hash.check_digest(digest).is_none());
+            if let Some((hash, digest)) = mismatch {
+                let mut mismatch = HashMismatch {
+                    url: self.url.clone(),
+                    algorithm: hash.algorithm(),
+                    expected: hash.expected().to_string(),
+                    actual: digest.to_hex(),
+                    size: content.size(),
+                    quarantined: None,
+                };
+                // Keep the bad content for later inspection
+                if let Some(dir) = &context.quarantine {
+                    match content.quarantine(dir, &self.file_name()) {
+                        Ok(path) => mismatch.quarantined = Some(path),
+                        Err(err) => warn!("Failed quarantining {} -- {err}", self.url),
+                    }
+                }
+                content.discard();
+                warn!("{mismatch}");
+                return Err(FileError::HashMismatch(Box::new(mismatch)));
+            }
+            digests = self.with_algorithms(computed);
+        }
+        // verify the signature
+        if let Some(signature) = &self.signature {
+            let bytes = match &signature.source {
+                SignatureSource::Url(url) => context.fetch(url),
+                SignatureSource::Bytes(bytes) => Ok(bytes.clone()),
+            };
+            let verified = bytes.map_err(FileError::Other).and_then(|bytes| {
+                let reader = content.reader()?;
+                signature
+                    .verify(&bytes, BufReader::new(reader))
+                    .map_err(|err| {
+                        FileError::Signature(format!("Invalid signature of {}: {err}", self.url))
+                    })
+            });
+            if let Err(err) = verified {
+                content.discard();
+                warn!("{err}");
+                return Err(err);
             }
         }
         // process the store
-        if let Some(store) = self.store {
-            let bytes = fs::read(&path).map_err(|e| e.to_string())?;
-            fs::remove_file(&path).map_err(|e| e.to_string())?;
-            store.create(bytes, self.path)?;
+        let mut path = self.path.clone();
+        let mut linked = false;
+        if let Some(store) = &self.store {
+            // The downloaded file is moved into the store without reading it in memory
+            let object = match &content {
+                Content::Path(path) => Some(store.adopt(path).inspect_err(|_| content.discard())?),
+                Content::Temp(temp) => Some(store.adopt(temp).inspect_err(|_| content.discard())?),
+                Content::Bytes(bytes) => Some(store.write(bytes.clone())?),
+                Content::Streamed(_) | Content::Current(_) => None,
+            };
+            match object {
+                Some(object) if self.path.eq(&PathBuf::new()) => path = object,
+                Some(object) => {
+                    let mode = self.link_mode.unwrap_or(store.link_mode());
+                    cas::link(&object, &self.path, mode)?;
+                    store.register_root(self.root())?;
+                    linked = true;
+                }
+                None => {}
+            }
         }
         // decompress the file
         if let Some(decompression) = self.decompression {
             indicator.event(indicator::Event::Stage(String::from("Extracting...")));
-            decompression.extract_file(&path)?;
+            let extracted = match &content {
+                Content::Path(_) | Content::Temp(_) => decompression.extract_file(&path),
+                Content::Current(current) => decompression.extract_file(current),
+                Content::Bytes(bytes) => decompression.extract(bytes.clone()),
+                Content::Streamed(_) => Ok(()),
+            };
+            if let Err(err) = extracted {
+                content.discard();
+                if linked {
+                    remove_partial(&self.path);
+                }
+                return Err(err.into());
+            }
         }
         indicator.event(indicator::Event::End);
-        Ok(())
+        Ok(Downloaded {
+            final_url: self.url.clone(),
+            redirects: Vec::new(),
+            digests,
+            from_store: false,
+            url: self.url,
+            path,
+            bytes: match (self.sink, content) {
+                (Some(Sink::Memory), Content::Bytes(bytes)) => Some(bytes),
+                _ => None,
+            },
+        })
+    }
+    /// Temporary file of the download of a file with a store
+    fn temp(&self) -> Result<Option<NamedTempFile>, String> {
+        let Some(store) = &self.store else {
+            return Ok(None);
+        };
+        // In the store directory the download is adopted with a rename
+        let temp = match store.root() {
+            Some(root) => fs::create_dir_all(root).and_then(|_| NamedTempFile::new_in(root)),
+            None => NamedTempFile::new(),
+        };
+        temp.map(Some).map_err(|e| e.to_string())
     }
-    fn path(&self) -> PathBuf {
-        if self.store.is_some() {
-            let path = NamedTempFile::new().unwrap();
-            return path.path().to_path_buf();
+    /// Path registered as root of the store object, the final path of a staged file
+    fn root(&self) -> &Path {
+        self.staged_from.as_deref().unwrap_or(&self.path)
+    }
+    /// Check if the file already exists in the path and matches the hashes
+    pub(crate) fn is_current(&self) -> bool {
+        self.current_digests().is_some()
+    }
+    /// Digests of the file in the path, if already exists and matches the hashes
+    fn current_digests(&self) -> Option<Digests> {
+        if self.hashes.is_empty() || self.sink.is_some() || !self.path.exists() {
+            return None;
+        }
+        let file = fs::File::open(&self.path).ok()?;
+        let digests = Hash::digest_all(&self.hashes, BufReader::new(file)).ok()?;
+        Hash::check_all(&self.hashes, &digests)?;
+        Some(self.with_algorithms(digests))
+    }
+    /// Object of the store with the expected content, looked up by a hash of the
+    /// algorithm of the store. The object is checked against every hash
+    fn stored_object(&self) -> Option<(PathBuf, Digests)> {
+        let store = self.store.as_ref()?;
+        if self.sink.is_some() || self.dir.is_some() {
+            return None;
+        }
+        let algorithm = store.algorithm()?;
+        let object = self
+            .hashes
+            .iter()
+            .filter(|hash| hash.algorithm() == Some(algorithm))
+            .filter_map(|hash| hex::decode(hash.expected()).ok())
+            .find_map(|digest| store.path(&Digest::new(digest)))?;
+        let file = fs::File::open(&object).ok()?;
+        let digests = Hash::digest_all(&self.hashes, BufReader::new(file)).ok()?;
+        if Hash::check_all(&self.hashes, &digests).is_none() {
+            warn!("The object {} is corrupted", object.to_string_lossy());
+            return None;
+        }
+        Some((object, self.with_algorithms(digests)))
+    }
+    /// Pair the digests with the algorithm of the hash in the same position
+    fn with_algorithms(&self, digests: Vec<Digest>) -> Digests {
+        self.hashes
+            .iter()
+            .map(Hash::algorithm)
+            .zip(digests)
+            .collect()
+    }
+    /// File name of the path or the last segment of the url
+    fn file_name(&self) -> String {
+        match self.path.file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => naming::sanitize(&naming::url_filename(&self.url).unwrap_or_default()),
+        }
+    }
+    /// Name of the file, the path file stem or the last segment of the url
+    fn name(&self) -> String {
+        match self.path.file_stem() {
+            Some(stem) => stem.to_string_lossy().to_string(),
+            None => naming::url_filename(&self.url).unwrap_or(self.url.clone()),
         }
-        self.path.clone()
     }
 }
 
+/// Downloaded content and the urls of the request
+struct Fetched {
+    /// Downloaded content
+    content: Content,
+    /// Final url after the redirects
+    final_url: String,
+    /// Urls that redirected the request
+    redirects: Vec<String>,
+}
+
+/// Downloaded content before the checks
+enum Content {
+    /// Content written in a path
+    Path(PathBuf),
+    /// Content written in a temporary file, to be adopted by the store
+    Temp(TempPath),
+    /// Content kept in memory
+    Bytes(Vec<u8>),
+    /// Content streamed to the sink and not kept, with the digests of the hashes
+    Streamed(Vec<Digest>),
+    /// Content already downloaded in a previous run
+    Current(PathBuf),
+}
+impl Content {
+    /// Read the content again
+    fn reader(&self) -> Result<Box<dyn Read + '_>, String> {
+        Ok(match self {
+            Content::Path(path) | Content::Current(path) => {
+                Box::new(std::fs::File::open(path).map_err(|e| e.to_string())?)
+            }
+            Content::Temp(temp) => Box::new(std::fs::File::open(temp).map_err(|e| e.to_string())?),
+            Content::Bytes(bytes) => Box::new(bytes.as_slice()),
+            Content::Streamed(_) => Box::new(std::io::empty()),
+        })
+    }
+    /// Size of the content written by this download
+    fn size(&self) -> u64 {
+        match self {
+            Content::Path(path) | Content::Current(path) => {
+                fs::metadata(path).map(|m| m.len()).unwrap_or_default()
+            }
+            Content::Temp(temp) => fs::metadata(temp).map(|m| m.len()).unwrap_or_default(),
+            Content::Bytes(bytes) => bytes.len() as u64,
+            Content::Streamed(_) => 0,
+        }
+    }
+    /// Move the content to the quarantine directory, returns the new path
+    fn quarantine(&self, dir: &Path, name: &str) -> Result<PathBuf, String> {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        let name = if name.is_empty() { "download" } else { name };
+        let mut path = dir.join(name);
+        let mut number = 0;
+        while path.symlink_metadata().is_ok() {
+            number += 1;
+            path = naming::numbered(&dir.join(name), number);
+        }
+        let src: &Path = match self {
+            Content::Path(src) => src,
+            Content::Temp(temp) => temp,
+            _ => Path::new(""),
+        };
+        match self {
+            Content::Path(_) | Content::Temp(_) => {
+                if fs::rename(src, &path).is_err() {
+                    fs::copy(src, &path).map_err(|e| e.to_string())?;
+                }
+            }
+            Content::Bytes(bytes) => fs::write(&path, bytes).map_err(|e| e.to_string())?,
+            Content::Streamed(_) | Content::Current(_) => {
+                return Err("The content was not kept".to_string());
+            }
+        }
+        debug!("Quarantined in {}", path.to_string_lossy());
+        Ok(path)
+    }
+    /// Remove the content written by this download
+    fn discard(&self) {
+        match self {
+            Content::Path(path) => remove_partial(path),
+            Content::Temp(temp) => remove_partial(temp),
+            _ => {}
+        }
+    }
+}
+
+/// Fetch a small auxiliary file, as a checksum file or a signature
+fn fetch(agent: &ureq::Agent, policy: &RedirectPolicy, url: &str) -> Result<Vec<u8>, String> {
+    let Routed { response, .. } = redirect::call(agent, "GET", url, &[], policy)?;
+    if response.status() != 200 {
+        return Err(format!(
+            "HTTP ERROR: {} fetching {url}",
+            response.status_text()
+        ));
+    }
+    let mut content = Vec::new();
+    response
+        .into_reader()
+        .read_to_end(&mut content)
+        .map_err(|e| e.to_string())?;
+    Ok(content)
+}
+
+/// Entries of a checksum file, fetched once per url
+pub(crate) fn cached_checksums(
+    checksums: &Mutex<HashMap<String, Checksums>>,
+    agent: &ureq::Agent,
+    policy: &RedirectPolicy,
+    url: &str,
+) -> Result<Vec<ChecksumEntry>, String> {
+    let entries = checksums
+        .lock()
+        .map_err(|e| e.to_string())?
+        .entry(url.to_string())
+        .or_default()
+        .clone();
+    // The map is unlocked during the request, only the files of the same url wait
+    entries
+        .get_or_init(|| {
+            let content = fetch(agent, policy, url)?;
+            Ok(checksum::parse(&String::from_utf8_lossy(&content), url))
+        })
+        .clone()
+}
+
+/// Remove a partial output, if exists
+fn remove_partial(path: &Path) {
+    if path.symlink_metadata().is_ok() {
+        debug!("Removing partial output {}", path.to_string_lossy());
+        if let Err(err) = fs::remove_file(path) {
+            warn!("Failed removing {} -- {err}", path.to_string_lossy());
+        }
+    }
+}
+
+/// Error of the downloads cancelled by the batch
+const CANCELLED: &str = "Cancelled";
+/// Error of the finished files of a transactional batch that failed
+const ROLLED_BACK: &str = "Rolled back";
+
+/// Behaviour of the batch when a file fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorMode {
+    /// Cancel the in-flight and pending downloads at the first failure
+    FailFast,
+    /// Finish all the downloads and return an aggregate error
+    #[default]
+    Continue,
+    /// Cancel the in-flight and pending downloads once n files have failed
+    Threshold(usize),
+}
+impl ErrorMode {
+    /// Check if the batch must be cancelled with the failed files
+    fn cancels(&self, failures: usize) -> bool {
+        match self {
+            Self::FailFast => failures > 0,
+            Self::Continue => false,
+            Self::Threshold(max) => failures >= *max,
+        }
+    }
+}
+
+/// Policy when two files of a batch resolve to the same path
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Conflict {
+    /// The last file overwrites the others
+    Overwrite,
+    /// Add a number to the file name (`file-1.tar.gz`)
+    #[default]
+    Rename,
+    /// Fail the file
+    Error,
+}
+
+/// Shared state of the downloads of a batch
+pub(crate) struct Context {
+    /// Agent to make the requests
+    agent: Arc<ureq::Agent>,
+    /// The batch was cancelled
+    cancel: AtomicBool,
+    /// Number of failed files
+    failures: AtomicUsize,
+    /// Policy of the name conflicts
+    conflict: Conflict,
+    /// Policy of the redirects
+    redirect: RedirectPolicy,
+    /// Size of the download buffer
+    buffer_size: usize,
+    /// Minimum time between two progress updates
+    progress_interval: Duration,
+    /// Downloaded bytes that force a progress update
+    progress_bytes: u64,
+    /// Directory where the content that don't matches the hashes is moved
+    quarantine: Option<PathBuf>,
+    /// Entries of the checksum files, each fetched once without blocking the other urls
+    checksums: Mutex<HashMap<String, Checksums>>,
+    /// Paths used by the files of the batch
+    claims: Mutex<HashSet<PathBuf>>,
+}
+impl Context {
+    /// Claim the path of a file, applying the conflict policy
+    /// if the path is used by other file
+    fn claim(&self, path: PathBuf) -> Result<PathBuf, String> {
+        let mut claims = self.claims.lock().unwrap();
+        if claims.insert(path.clone()) {
+            return Ok(path);
+        }
+        match self.conflict {
+            Conflict::Overwrite => Ok(path),
+            Conflict::Error => Err(format!(
+                "The path {} is used by other file",
+                path.to_string_lossy()
+            )),
+            Conflict::Rename => {
+                let path = (1..)
+                    .map(|number| naming::numbered(&path, number))
+                    .find(|path| !claims.contains(path))
+                    .unwrap();
+                claims.insert(path.clone());
+                Ok(path)
+            }
+        }
+    }
+    /// Check if the batch was cancelled
+    fn cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+    /// Entries of a checksum file, fetched once per batch
+    fn checksums(&self, url: &str) -> Result<Vec<ChecksumEntry>, String> {
+        cached_checksums(&self.checksums, &self.agent, &self.redirect, url)
+    }
+    /// Fetch a small auxiliary file, as a checksum file or a signature
+    fn fetch(&self, url: &str) -> Result<Vec<u8>, String> {
+        fetch(&self.agent, &self.redirect, url)
+    }
+}
+
+/// Result of a finished file download
+#[derive(Debug, Clone)]
+pub struct Downloaded {
+    /// Url of the asset
+    pub url: String,
+    /// Path of the file, or the store object when the file only has a sink
+    pub path: PathBuf,
+    /// Content of the file when the sink is [Sink::Memory]
+    pub bytes: Option<Vec<u8>>,
+    /// Final url after the redirects
+    pub final_url: String,
+    /// Urls that redirected the request, in order
+    pub redirects: Vec<String>,
+    /// Computed digests, in the order of the hashes of the file
+    pub digests: Vec<(Option<HashAlgorithm>, Digest)>,
+    /// The content was found in the CAS store, without a request
+    pub from_store: bool,
+}
+
 pub struct Downloader {
     indicator: Box<dyn IndicatorFactory + Send + Sync>,
     files: Vec<File>,
     max_current_downloads: usize,
     agent: Arc<ureq::Agent>,
+    cache: Option<Arc<HttpCache>>,
+    space_check: bool,
+    extraction_ratio: f64,
+    error_mode: ErrorMode,
+    transaction: bool,
+    staging_dir: Option<PathBuf>,
+    conflict: Conflict,
+    redirect: RedirectPolicy,
+    events: Option<Sender<DownloadEvent>>,
+    buffer_size: usize,
+    progress_interval: Duration,
+    progress_bytes: u64,
+    quarantine: Option<PathBuf>,
+    checksum_url: Option<String>,
+    /// Entries of the checksum files fetched by the plan, reused by the downloads
+    checksums: Mutex<HashMap<String, Checksums>>,
 }
 impl Downloader {
     pub fn new<T: IndicatorFactory + Sync + Send + 'static>(indicator: T) -> Self {
@@ -223,9 +1051,28 @@ impl Downloader {
             indicator: Box::new(indicator),
             files: Vec::new(),
             max_current_downloads: 5,
-            agent: Arc::new(ureq::agent()),
+            // The redirects are followed by the downloader to apply the policy
+            agent: Arc::new(ureq::AgentBuilder::new().redirects(0).build()),
+            cache: None,
+            space_check: false,
+            extraction_ratio: space::EXTRACTION_RATIO,
+            error_mode: ErrorMode::default(),
+            transaction: false,
+            staging_dir: None,
+            conflict: Conflict::default(),
+            redirect: RedirectPolicy::default(),
+            events: None,
+            buffer_size: CHUNK_SIZE,
+            progress_interval: PROGRESS_INTERVAL,
+            progress_bytes: PROGRESS_BYTES,
+            quarantine: None,
+            checksum_url: None,
+            checksums: Mutex::new(HashMap::new()),
         }
     }
+    /// Sets the agent of the requests, it should be built with `redirects(0)`
+    /// so the redirect policy is applied at every hop. The redirects followed by the agent
+    /// fail if the policy restricts the hosts, the schemes or the hops
     pub fn with_ureq_agent(mut self, agent: ureq::Agent) -> Self {
         self.agent = Arc::new(agent);
         self
@@ -234,6 +1081,50 @@ impl Downloader {
         self.max_current_downloads = max_current_downloads;
         self
     }
+    /// Sets the http response cache used by the files without a pinned hash
+    pub fn with_cache(mut self, cache: Arc<HttpCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+    /// Check the available space before start the downloads
+    pub fn with_space_check(mut self, space_check: bool) -> Self {
+        self.space_check = space_check;
+        self
+    }
+    /// Sets the estimation of the extracted size of an archive, relative to the archive size
+    pub fn with_extraction_ratio(mut self, ratio: f64) -> Self {
+        self.extraction_ratio = ratio;
+        self
+    }
+    /// Sets the behaviour of the batch when a file fails
+    pub fn with_error_mode(mut self, error_mode: ErrorMode) -> Self {
+        self.error_mode = error_mode;
+        self
+    }
+    /// Sets the transactional mode, the files and extractions are staged and only
+    /// moved to the final paths after the whole batch succeeded.
+    /// The store objects, the registered roots and the http cache entries are written
+    /// outside the staging area and are kept after a rollback
+    pub fn with_transaction(mut self, transaction: bool) -> Self {
+        self.transaction = transaction;
+        self
+    }
+    /// Sets the directory of the staging area of the transactions, default the temp dir.
+    /// In the same filesystem of the destinations the commit is a rename
+    pub fn with_staging_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.staging_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+    /// Sets the policy when two files resolve to the same path
+    pub fn with_conflict(mut self, conflict: Conflict) -> Self {
+        self.conflict = conflict;
+        self
+    }
+    /// Sets the policy applied to the urls and every redirect hop
+    pub fn with_redirect_policy(mut self, redirect: RedirectPolicy) -> Self {
+        self.redirect = redirect;
+        self
+    }
     pub fn with_file(mut self, file: File) -> Self {
         self.files.push(file);
         self
@@ -242,6 +1133,37 @@ impl Downloader {
         self.files = files;
         self
     }
+    /// Sets the size of the download buffer of the files, default [CHUNK_SIZE]
+    pub fn with_buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size;
+        self
+    }
+    /// Sets how often the progress is updated, after `interval` or `bytes` downloaded,
+    /// whichever comes first. Default [PROGRESS_INTERVAL] and [PROGRESS_BYTES]
+    pub fn with_progress_interval(mut self, interval: Duration, bytes: u64) -> Self {
+        self.progress_interval = interval;
+        self.progress_bytes = bytes;
+        self
+    }
+    /// Sets the directory where the content that don't matches the hashes is moved
+    /// for later inspection, by default the content is removed
+    pub fn with_quarantine_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.quarantine = Some(dir.as_ref().to_path_buf());
+        self
+    }
+    /// Sets the url of a checksum file (`SHA256SUMS`) used by the files without hashes,
+    /// the file is fetched once and every file must have an entry
+    pub fn with_checksum_url(mut self, url: &str) -> Self {
+        self.checksum_url = Some(url.to_string());
+        self
+    }
+    /// Receiver of the events of the batch, tagged with the position of the file.
+    /// The indicators keep receiving the events
+    pub fn events(&mut self) -> Receiver<DownloadEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.events = Some(sender);
+        receiver
+    }
     pub fn with_indicator<T: IndicatorFactory + Send + Sync + 'static>(
         mut self,
         indicator: T,
@@ -249,38 +1171,145 @@ impl Downloader {
         self.indicator = Box::new(indicator);
         self
     }
-    pub fn start(self) -> Result<(), String> {
+    /// Download all the files, returns the result of the finished downloads
+    pub fn start(mut self) -> Result<Vec<Downloaded>, BatchError> {
+        if self.space_check {
+            self.check_space()?;
+        }
+        // Dropping the staging area rolls back the batch
+        let staging = match self.transaction {
+            true => Some(Staging::stage(
+                &mut self.files,
+                self.staging_dir.as_deref(),
+            )?),
+            false => None,
+        };
         let mut handles = Vec::new();
         let semaphore = Arc::new(Semaphore::new(self.max_current_downloads));
         let factory = Arc::new(Mutex::new(self.indicator));
-        let agent = self.agent;
-        for file in self.files {
+        let context = Arc::new(Context {
+            agent: self.agent,
+            cancel: AtomicBool::new(false),
+            failures: AtomicUsize::new(0),
+            conflict: self.conflict,
+            redirect: self.redirect,
+            buffer_size: self.buffer_size,
+            progress_interval: self.progress_interval,
+            progress_bytes: self.progress_bytes,
+            quarantine: self.quarantine,
+            checksums: self.checksums,
+            claims: Mutex::new(
+                self.files
+                    .iter()
+                    .filter(|file| file.dir.is_none() && !file.path.as_os_str().is_empty())
+                    .map(|file| file.path.clone())
+                    .collect(),
+            ),
+        });
+        let error_mode = self.error_mode;
+        let transaction = staging.is_some();
+        let total = self.files.len();
+        for (id, mut file) in self.files.into_iter().enumerate() {
+            if file.checksum_url.is_none() && file.hashes.is_empty() {
+                file.checksum_url = self.checksum_url.clone();
+            }
+            // The streamed content isn't kept to be cached
+            if file.cache.is_none()
+                && file.hashes.is_empty()
+                && file.checksum_url.is_none()
+                && !file.sink.as_ref().is_some_and(Sink::streams)
+            {
+                file.cache = self.cache.clone();
+            }
             let semaphore = semaphore.clone();
             let factory = factory.clone();
-            let agent = agent.clone();
+            let context = context.clone();
+            let events = self.events.clone();
+            let url = file.url.clone();
+            events::send(&events, DownloadEvent::Queued { id, url });
             handles.push(thread::spawn(move || {
-                let factory = factory.clone();
-                let semaphore = semaphore.clone();
-                let agent = agent.clone();
+                let url = file.url.clone();
                 semaphore.acquire();
+                // The batch was cancelled while the file was pending
+                if context.cancelled() {
+                    semaphore.release();
+                    let err = FileError::Other(CANCELLED.to_string());
+                    if !transaction {
+                        let result = Err(err.clone());
+                        events::send(&events, DownloadEvent::Finished { id, result });
+                    }
+                    return Err((url, err));
+                }
+                let name = file.name();
+                events::send(
+                    &events,
+                    DownloadEvent::Started {
+                        id,
+                        name: name.clone(),
+                    },
+                );
                 let mut indicator = {
                     let mut fac = factory.lock().unwrap();
-                    fac.create(
-                        file.path.file_stem().unwrap().to_string_lossy().to_string(),
-                        file.size as usize,
-                    )
+                    fac.create(name, file.size as usize)
                 };
-                let err = file.download(agent, &mut indicator);
-                if let Err(err) = err {
-                    indicator.event(indicator::Event::Error(err));
+                if let Some(events) = &events {
+                    indicator = Box::new(EventIndicator::new(id, indicator, events.clone()));
+                }
+                let result = file.download(&context, &mut indicator);
+                if let Err(err) = &result {
+                    indicator.event(indicator::Event::Error(err.to_string()));
+                    if !err.is_cancelled() {
+                        let failures = context.failures.fetch_add(1, Ordering::SeqCst) + 1;
+                        if error_mode.cancels(failures) {
+                            context.cancel.store(true, Ordering::SeqCst);
+                        }
+                    }
                 }
                 semaphore.release();
+                // The result of a transactional batch is known after the commit
+                if !transaction {
+                    let result = result.clone();
+                    events::send(&events, DownloadEvent::Finished { id, result });
+                }
+                result.map_err(|err| (url, err))
             }));
         }
-        for handle in handles {
-            handle.join().unwrap();
+        let results: Vec<Result<Downloaded, (String, FileError)>> = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+        if results.iter().any(Result::is_err) {
+            let failures = context.failures.load(Ordering::SeqCst);
+            let mut message = format!("{failures} of {total} files failed");
+            for (url, error) in results.iter().filter_map(|result| result.as_ref().err()) {
+                message.push_str(&format!("\n - {url}: {error}"));
+            }
+            let failures: Vec<(String, FileError)> = results
+                .iter()
+                .filter_map(|result| result.as_ref().err().cloned())
+                .collect();
+            if transaction {
+                for (id, result) in results.into_iter().enumerate() {
+                    let rolled_back = FileError::Other(ROLLED_BACK.to_string());
+                    let result = Err(result.err().map_or(rolled_back, |(_, err)| err));
+                    events::send(&self.events, DownloadEvent::Finished { id, result });
+                }
+            }
+            return Err(BatchError { message, failures });
+        }
+        let mut downloaded: Vec<Downloaded> = results.into_iter().flatten().collect();
+        if let Some(staging) = staging {
+            let committed = staging.commit(&mut downloaded);
+            for (id, file) in downloaded.iter().enumerate() {
+                let result = committed
+                    .clone()
+                    .map(|_| file.clone())
+                    .map_err(FileError::Other);
+                events::send(&self.events, DownloadEvent::Finished { id, result });
+            }
+            committed?;
         }
 
-        Ok(())
+        Ok(downloaded)
     }
 }
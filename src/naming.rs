@@ -0,0 +1,103 @@
+use std::path::{Path, PathBuf};
+
+/// Name used when nothing gives a valid file name
+const FALLBACK: &str = "download";
+
+/// Resolve the file name of a response, from the `Content-Disposition` header,
+/// then the final url, then the original url
+pub(crate) fn resolve(disposition: Option<&str>, final_url: &str, url: &str) -> String {
+    [
+        disposition.and_then(disposition_filename),
+        url_filename(final_url),
+        url_filename(url),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|name| sanitize(&name))
+    .find(|name| !name.is_empty())
+    .unwrap_or(FALLBACK.to_string())
+}
+
+/// File name of a `Content-Disposition` header, `filename*` has preference
+fn disposition_filename(header: &str) -> Option<String> {
+    let mut plain = None;
+    for param in header.split(';') {
+        let Some((key, value)) = param.split_once('=') else {
+            continue;
+        };
+        let value = value.trim().trim_matches('"');
+        match key.trim().to_ascii_lowercase().as_str() {
+            // RFC 5987 value: charset'language'percent-encoded
+            "filename*" => {
+                if let Some(encoded) = value.splitn(3, '\'').nth(2) {
+                    return Some(percent_decode(encoded));
+                }
+            }
+            "filename" => plain = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    plain
+}
+
+/// Last segment of the url path
+pub(crate) fn url_filename(url: &str) -> Option<String> {
+    let path = url.split(['?', '#']).next()?;
+    let path = path.split_once("://").map(|(_, rest)| rest).unwrap_or(path);
+    // The host is not a file name
+    let (_, path) = path.split_once('/')?;
+    path.rsplit('/')
+        .find(|segment| !segment.is_empty())
+        .map(percent_decode)
+}
+
+/// Remove the unsafe characters of a file name
+pub(crate) fn sanitize(name: &str) -> String {
+    // Keep only the last component, the name can't traverse directories
+    let name = name.rsplit(['/', '\\']).next().unwrap_or(name);
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let name = name.trim_matches(|c: char| c == '.' || c.is_whitespace());
+    name.to_string()
+}
+
+/// Add a number to the file name, before the extensions (`file-1.tar.gz`)
+pub(crate) fn numbered(path: &Path, number: usize) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let name = match name.split_once('.') {
+        Some((stem, ext)) => format!("{stem}-{number}.{ext}"),
+        None => format!("{name}-{number}"),
+    };
+    path.with_file_name(name)
+}
+
+/// Decode the `%XX` sequences of a string
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && i + 2 < bytes.len()
+            && let Some(byte) = std::str::from_utf8(&bytes[i + 1..i + 3])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            decoded.push(byte);
+            i += 3;
+            continue;
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
@@ -0,0 +1,169 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use tracing::debug;
+
+use crate::{
+    Downloader, File, cached_checksums, naming,
+    redirect::{self, RedirectPolicy, Routed},
+};
+
+/// Action that the downloader would do with a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// The file would be downloaded
+    Download,
+    /// The file exists and matches the hash, or the content is in the store,
+    /// the download would be skipped
+    Skip,
+}
+
+/// Planned operations of a file
+#[derive(Debug, Clone)]
+pub struct PlannedFile {
+    /// Url of the asset
+    pub url: String,
+    /// Destination path, empty if the file has a sink
+    pub path: PathBuf,
+    /// Action over the file
+    pub action: Action,
+    /// Declared size, or discovered with a HEAD request
+    pub size: Option<u64>,
+    /// Availability of the url, `None` if no HEAD request was issued
+    pub available: Option<bool>,
+    /// The content would be stored in the CAS store
+    pub store: bool,
+    /// Destination of the extraction, if the file would be extracted
+    pub extract: Option<PathBuf>,
+    /// The destination path exists and would be overwritten
+    pub overwrite: bool,
+}
+
+/// Result of planning a batch without writing anything
+#[derive(Debug, Clone)]
+pub struct Plan {
+    /// Planned files, in the same order of the downloader
+    pub files: Vec<PlannedFile>,
+    /// Total bytes to fetch of the files with a known size
+    pub total_bytes: u64,
+    /// Files that would be downloaded but the size is unknown
+    pub unknown_sizes: usize,
+    /// Destination paths used by more than one file
+    pub collisions: Vec<PathBuf>,
+}
+impl Plan {
+    /// Files that would be downloaded
+    pub fn downloads(&self) -> impl Iterator<Item = &PlannedFile> {
+        self.files.iter().filter(|f| f.action == Action::Download)
+    }
+    /// Files that would be skipped
+    pub fn skips(&self) -> impl Iterator<Item = &PlannedFile> {
+        self.files.iter().filter(|f| f.action == Action::Skip)
+    }
+}
+
+impl Downloader {
+    /// Resolve every file without writing anything.
+    /// If `head` is true, issue a HEAD request per downloaded file to know the size and availability.
+    /// The checksum files are fetched to know the hashes of the files, once per downloader
+    pub fn plan(&self, head: bool) -> Plan {
+        let files: Vec<PlannedFile> = self
+            .files
+            .iter()
+            .map(|file| {
+                let mut file = file.clone();
+                if file.checksum_url.is_none() && file.hashes.is_empty() {
+                    file.checksum_url = self.checksum_url.clone();
+                }
+                let resolved = file.resolve_checksum(|url| {
+                    cached_checksums(&self.checksums, &self.agent, &self.redirect, url)
+                });
+                // Without the hash the file can't be current, it's downloaded
+                if let Err(err) = resolved {
+                    debug!("Unknown hash of {} -- {err}", file.url);
+                }
+                file.plan(&self.agent, &self.redirect, head)
+            })
+            .collect();
+
+        let mut total_bytes = 0;
+        let mut unknown_sizes = 0;
+        for file in files.iter().filter(|f| f.action == Action::Download) {
+            match file.size {
+                Some(size) => total_bytes += size,
+                None => unknown_sizes += 1,
+            }
+        }
+
+        let mut uses: HashMap<&PathBuf, usize> = HashMap::new();
+        for file in files.iter().filter(|f| !f.path.as_os_str().is_empty()) {
+            *uses.entry(&file.path).or_default() += 1;
+        }
+        let mut collisions: Vec<PathBuf> = uses
+            .into_iter()
+            .filter(|(_, uses)| *uses > 1)
+            .map(|(path, _)| path.clone())
+            .collect();
+        collisions.sort();
+
+        Plan {
+            files,
+            total_bytes,
+            unknown_sizes,
+            collisions,
+        }
+    }
+}
+
+impl File {
+    /// Plan the operations of the file
+    fn plan(&self, agent: &ureq::Agent, policy: &RedirectPolicy, head: bool) -> PlannedFile {
+        let action = if self.is_current() || self.stored_object().is_some() {
+            Action::Skip
+        } else {
+            Action::Download
+        };
+        let mut size = (self.size != 0).then_some(self.size);
+        let mut available = None;
+        let mut path = match &self.dir {
+            Some(dir) => dir.join(naming::resolve(None, &self.url, &self.url)),
+            None => self.path.clone(),
+        };
+        // The HEAD request is only needed for an unknown size or name
+        if head && action == Action::Download && (size.is_none() || self.dir.is_some()) {
+            match redirect::call(agent, "HEAD", &self.url, &[], policy) {
+                Ok(Routed { response, .. }) => {
+                    available = Some(true);
+                    if let Some(dir) = &self.dir {
+                        let name = naming::resolve(
+                            response.header("Content-Disposition"),
+                            response.get_url(),
+                            &self.url,
+                        );
+                        path = dir.join(name);
+                    }
+                    if size.is_none() {
+                        size = response
+                            .header("Content-Length")
+                            .and_then(|len| len.parse().ok());
+                    }
+                }
+                Err(err) => {
+                    debug!("HEAD request of {} failed -- {err}", self.url);
+                    available = Some(false);
+                }
+            }
+        }
+        let overwrite =
+            action == Action::Download && self.sink.is_none() && path.symlink_metadata().is_ok();
+        PlannedFile {
+            url: self.url.clone(),
+            path,
+            action,
+            size,
+            available,
+            store: self.store.is_some(),
+            extract: self.decompression.as_ref().map(|d| d.dst.clone()),
+            overwrite,
+        }
+    }
+}
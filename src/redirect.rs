@@ -0,0 +1,140 @@
+use tracing::{debug, info};
+use url::Url;
+
+/// Policy applied to the url of the request and every redirect hop
+#[derive(Debug, Clone)]
+pub struct RedirectPolicy {
+    /// Maximum number of redirects
+    max_redirects: usize,
+    /// Refuse the urls without https
+    https_only: bool,
+    /// Allowed hosts, all hosts are allowed if is empty
+    allow: Vec<String>,
+    /// Denied hosts
+    deny: Vec<String>,
+}
+impl Default for RedirectPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl RedirectPolicy {
+    /// Create a new policy, follows 5 redirects to any host
+    pub fn new() -> Self {
+        Self {
+            max_redirects: 5,
+            https_only: false,
+            allow: Vec::new(),
+            deny: Vec::new(),
+        }
+    }
+    /// Sets the maximum number of redirects
+    pub fn with_max_redirects(mut self, max_redirects: usize) -> Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+    /// Refuse the urls without https, as a downgrade to http in a redirect
+    pub fn with_https_only(mut self, https_only: bool) -> Self {
+        self.https_only = https_only;
+        self
+    }
+    /// Add an allowed host, the subdomains are also allowed.
+    /// When the allowlist is not empty, only the listed hosts are allowed
+    pub fn with_allowed_host<S: ToString>(mut self, host: S) -> Self {
+        self.allow.push(host.to_string().to_ascii_lowercase());
+        self
+    }
+    /// Add a denied host, the subdomains are also denied
+    pub fn with_denied_host<S: ToString>(mut self, host: S) -> Self {
+        self.deny.push(host.to_string().to_ascii_lowercase());
+        self
+    }
+    /// Check if the policy restricts the hosts, the schemes or the hops
+    /// more than the default policy and the default agent of ureq
+    fn restricts(&self) -> bool {
+        self.https_only
+            || !self.allow.is_empty()
+            || !self.deny.is_empty()
+            || self.max_redirects < Self::new().max_redirects
+    }
+    /// Check if the policy allows the url
+    pub fn check(&self, url: &Url) -> Result<(), String> {
+        if self.https_only && url.scheme() != "https" {
+            return Err(format!("Refused non https url: {url}"));
+        }
+        let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+        let matches = |pattern: &String| host == *pattern || host.ends_with(&format!(".{pattern}"));
+        if self.deny.iter().any(matches) {
+            return Err(format!("Refused denied host: {host}"));
+        }
+        if !self.allow.is_empty() && !self.allow.iter().any(matches) {
+            return Err(format!("Refused host not allowed: {host}"));
+        }
+        Ok(())
+    }
+}
+
+/// Response of a request and the urls that answered with a redirect
+pub(crate) struct Routed {
+    /// Final response
+    pub response: ureq::Response,
+    /// Urls that redirected the request, in order
+    pub redirects: Vec<String>,
+}
+
+/// Make a request following the redirects allowed by the policy.
+/// The hops followed by the agent can't be checked, they are only accepted
+/// with a permissive policy, and only the final url is recorded
+pub(crate) fn call(
+    agent: &ureq::Agent,
+    method: &str,
+    url: &str,
+    headers: &[(&str, &str)],
+    policy: &RedirectPolicy,
+) -> Result<Routed, String> {
+    let mut url = Url::parse(url).map_err(|e| e.to_string())?;
+    policy.check(&url)?;
+    let mut redirects = Vec::new();
+    loop {
+        let mut request = agent.request_url(method, &url);
+        for (name, value) in headers {
+            request = request.set(name, value);
+        }
+        let response = request.call().map_err(|e| e.to_string())?;
+        let current = Url::parse(response.get_url()).map_err(|e| e.to_string())?;
+        if current != url {
+            if policy.restricts() {
+                return Err(format!(
+                    "The agent followed the redirects of {url} to {current}, build it with `redirects(0)`"
+                ));
+            }
+            policy.check(&current)?;
+            redirects.push(url.to_string());
+        }
+        let location = match response.status() {
+            301 | 302 | 303 | 307 | 308 => response.header("Location"),
+            _ => None,
+        };
+        let Some(location) = location else {
+            if !redirects.is_empty() {
+                info!("{} redirected to {}", redirects[0], current);
+            }
+            return Ok(Routed {
+                response,
+                redirects,
+            });
+        };
+        if redirects.len() >= policy.max_redirects {
+            return Err(format!(
+                "Too many redirects ({}) requesting {}",
+                policy.max_redirects,
+                redirects.first().unwrap_or(&current.to_string())
+            ));
+        }
+        let next = current.join(location).map_err(|e| e.to_string())?;
+        policy.check(&next)?;
+        debug!("Redirect {} {} -> {}", response.status(), current, next);
+        redirects.push(current.to_string());
+        url = next;
+    }
+}
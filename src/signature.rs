@@ -0,0 +1,148 @@
+use std::io::Read;
+
+/// Source of a detached signature
+#[derive(Debug, Clone)]
+pub enum SignatureSource {
+    /// Url of the signature, fetched before the verification
+    Url(String),
+    /// Content of the signature
+    Bytes(Vec<u8>),
+}
+impl From<&str> for SignatureSource {
+    fn from(url: &str) -> Self {
+        Self::Url(url.to_string())
+    }
+}
+impl From<String> for SignatureSource {
+    fn from(url: String) -> Self {
+        Self::Url(url)
+    }
+}
+impl From<Vec<u8>> for SignatureSource {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::Bytes(bytes)
+    }
+}
+
+/// Public key that verifies the signatures,
+/// only the keys of the enabled features are available
+#[derive(Debug, Clone)]
+pub enum PublicKey {
+    /// Minisign key, the key file or the base64 line
+    #[cfg(feature = "minisign")]
+    Minisign(String),
+    /// Signify key, the key file or the base64 line
+    #[cfg(feature = "ed25519")]
+    Signify(String),
+    /// Raw ed25519 key, the signature is the raw 64 bytes, hex or base64
+    #[cfg(feature = "ed25519")]
+    Ed25519([u8; 32]),
+}
+
+/// Detached signature of a file
+#[derive(Debug, Clone)]
+pub struct Signature {
+    /// Source of the signature
+    pub source: SignatureSource,
+    /// Key of the signer
+    pub key: PublicKey,
+}
+impl Signature {
+    /// Verify the signature of the content
+    #[allow(unused_variables, unused_mut)]
+    pub(crate) fn verify<R: Read>(&self, signature: &[u8], mut content: R) -> Result<(), String> {
+        match &self.key {
+            #[cfg(feature = "minisign")]
+            PublicKey::Minisign(key) => minisign(key, signature, content),
+            #[cfg(feature = "ed25519")]
+            PublicKey::Signify(key) => {
+                let (key_id, key) = signify_decode::<32>(key.as_bytes())?;
+                let (sig_id, signature) = signify_decode::<64>(signature)?;
+                if key_id != sig_id {
+                    return Err("The signature was made with other key".to_string());
+                }
+                ed25519(&key, &signature, content)
+            }
+            #[cfg(feature = "ed25519")]
+            PublicKey::Ed25519(key) => {
+                let signature = match <[u8; 64]>::try_from(signature) {
+                    Ok(signature) => signature,
+                    Err(_) => {
+                        let text = String::from_utf8_lossy(signature);
+                        crate::hash::normalize(&text, Some(64))
+                            .and_then(|hex| hex::decode(hex).ok())
+                            .and_then(|bytes| <[u8; 64]>::try_from(bytes).ok())
+                            .ok_or("Invalid ed25519 signature")?
+                    }
+                };
+                ed25519(key, &signature, content)
+            }
+            #[allow(unreachable_patterns)]
+            _ => Err("Signatures are not supported, enable a signature feature".to_string()),
+        }
+    }
+}
+
+/// Verify a minisign signature, the prehashed signatures are verified in chunks
+#[cfg(feature = "minisign")]
+fn minisign<R: Read>(key: &str, signature: &[u8], mut content: R) -> Result<(), String> {
+    let key = minisign_verify::PublicKey::decode(key)
+        .or_else(|_| minisign_verify::PublicKey::from_base64(key.trim()))
+        .map_err(|e| format!("Invalid minisign key: {e}"))?;
+    let signature = minisign_verify::Signature::decode(&String::from_utf8_lossy(signature))
+        .map_err(|e| format!("Invalid minisign signature: {e}"))?;
+    match key.verify_stream(&signature) {
+        Ok(mut verifier) => {
+            let mut buffer = vec![0u8; crate::CHUNK_SIZE];
+            loop {
+                let size = content.read(&mut buffer).map_err(|e| e.to_string())?;
+                if size == 0 {
+                    break;
+                }
+                verifier.update(&buffer[..size]);
+            }
+            verifier.finalize()
+        }
+        // The legacy signatures sign the whole content
+        Err(minisign_verify::Error::UnsupportedLegacyMode) => {
+            let mut bytes = Vec::new();
+            content.read_to_end(&mut bytes).map_err(|e| e.to_string())?;
+            key.verify(&bytes, &signature, true)
+        }
+        Err(err) => Err(err),
+    }
+    .map_err(|e| e.to_string())
+}
+
+/// Decode a signify key or signature, `Ed` + key id + content in base64
+#[cfg(feature = "ed25519")]
+fn signify_decode<const N: usize>(file: &[u8]) -> Result<([u8; 8], [u8; N]), String> {
+    use base64::{Engine, engine::general_purpose::STANDARD};
+    let file = String::from_utf8_lossy(file);
+    // The base64 line follows the untrusted comment
+    let line = file
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with("untrusted comment:"))
+        .ok_or("Empty signify file")?;
+    let bytes = STANDARD.decode(line).map_err(|e| e.to_string())?;
+    if bytes.len() != 10 + N || &bytes[..2] != b"Ed" {
+        return Err("Invalid signify file".to_string());
+    }
+    let mut key_id = [0u8; 8];
+    key_id.copy_from_slice(&bytes[2..10]);
+    let mut content = [0u8; N];
+    content.copy_from_slice(&bytes[10..]);
+    Ok((key_id, content))
+}
+
+/// Verify an ed25519 signature, the whole content is signed
+#[cfg(feature = "ed25519")]
+fn ed25519<R: Read>(key: &[u8; 32], signature: &[u8; 64], mut content: R) -> Result<(), String> {
+    let key = ed25519_dalek::VerifyingKey::from_bytes(key).map_err(|e| e.to_string())?;
+    let signature = ed25519_dalek::Signature::from_bytes(signature);
+    let mut bytes = Vec::new();
+    content.read_to_end(&mut bytes).map_err(|e| e.to_string())?;
+    key.verify_strict(&bytes, &signature)
+        .map_err(|e| e.to_string())
+}
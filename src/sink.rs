@@ -0,0 +1,70 @@
+use std::{
+    fmt::Debug,
+    io::{self, Write},
+    sync::{Arc, Mutex, mpsc::Sender},
+};
+
+/// Destination of the downloaded bytes for files without a path
+#[derive(Clone)]
+pub enum Sink {
+    /// Keep the content in memory and return it in the result
+    Memory,
+    /// Write the content in a writer
+    Writer(Arc<Mutex<dyn Write + Send>>),
+    /// Send the content in chunks through a channel
+    Channel(Sender<Vec<u8>>),
+}
+impl Sink {
+    /// Create a sink from a writer
+    pub fn writer<W: Write + Send + 'static>(writer: W) -> Self {
+        Self::Writer(Arc::new(Mutex::new(writer)))
+    }
+    /// Check if the content is streamed without keeping it
+    pub(crate) fn streams(&self) -> bool {
+        !matches!(self, Self::Memory)
+    }
+    /// Create the writer that receives the downloaded chunks
+    pub(crate) fn open(&self) -> Box<dyn Write + Send> {
+        match self {
+            Self::Memory => Box::new(io::sink()),
+            Self::Writer(writer) => Box::new(SharedWriter(writer.clone())),
+            Self::Channel(sender) => Box::new(ChannelWriter(sender.clone())),
+        }
+    }
+}
+impl Debug for Sink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Memory => write!(f, "Memory"),
+            Self::Writer(_) => write!(f, "Writer"),
+            Self::Channel(_) => write!(f, "Channel"),
+        }
+    }
+}
+
+/// Writer shared with the user
+struct SharedWriter(Arc<Mutex<dyn Write + Send>>);
+impl Write for SharedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut writer = self.0.lock().map_err(|e| io::Error::other(e.to_string()))?;
+        writer.write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        let mut writer = self.0.lock().map_err(|e| io::Error::other(e.to_string()))?;
+        writer.flush()
+    }
+}
+
+/// Writer that sends every chunk through a channel
+struct ChannelWriter(Sender<Vec<u8>>);
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .send(buf.to_vec())
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "Channel closed"))?;
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+use std::{
+    collections::HashMap,
+    env, io,
+    path::{Path, PathBuf},
+};
+
+use tracing::debug;
+
+use crate::{Downloader, plan::Action};
+
+/// Default estimation of the extracted size of an archive
+pub const EXTRACTION_RATIO: f64 = 3.0;
+
+impl Downloader {
+    /// Compare the space required by the batch with the available space of each target filesystem.
+    /// The size of the files is the declared size or discovered with a HEAD request,
+    /// the files with unknown size are ignored. In a transaction the outputs are also
+    /// written in the staging area
+    pub fn check_space(&self) -> Result<(), String> {
+        let plan = self.plan(true);
+        let temp = env::temp_dir();
+        let staging = self
+            .transaction
+            .then(|| self.staging_dir.clone().unwrap_or_else(|| temp.clone()));
+        let mut required = Required::default();
+        for (file, planned) in self.files.iter().zip(&plan.files) {
+            let Some(size) = planned.size else {
+                debug!("Unknown size of {}, ignored in the space check", file.url);
+                continue;
+            };
+            if planned.action == Action::Download {
+                if let Some(store) = &file.store {
+                    // Downloaded in the store directory and adopted with a rename,
+                    // a store without directory is written from a temporary file
+                    required.add(store.root().unwrap_or(&temp), size)?;
+                } else if file.sink.is_none() {
+                    required.add_output(&planned.path, staging.as_deref(), size)?;
+                }
+            }
+            if let Some(dst) = &planned.extract {
+                // Temporary extraction and the copy to the destination
+                let extracted = (size as f64 * self.extraction_ratio) as u64;
+                required.add(&temp, extracted)?;
+                // The verified copy of the extraction with a tree hash
+                if file
+                    .decompression
+                    .as_ref()
+                    .is_some_and(|d| d.tree.is_some())
+                {
+                    required.add(&temp, extracted)?;
+                }
+                required.add_output(dst, staging.as_deref(), extracted)?;
+            }
+        }
+        for (path, bytes) in required.filesystems.into_values() {
+            let available = fs4::available_space(&path).map_err(|e| e.to_string())?;
+            debug!(
+                "{} bytes required in {}, {} available",
+                bytes,
+                path.to_string_lossy(),
+                available
+            );
+            if bytes > available {
+                return Err(format!(
+                    "Not enough space in {}: {} bytes required, {} available",
+                    path.to_string_lossy(),
+                    bytes,
+                    available
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Required bytes per filesystem
+#[derive(Default)]
+struct Required {
+    filesystems: HashMap<String, (PathBuf, u64)>,
+}
+impl Required {
+    /// Add the bytes to the filesystem of the path
+    fn add(&mut self, path: &Path, bytes: u64) -> Result<(), String> {
+        let existing = existing_ancestor(path).map_err(|e| e.to_string())?;
+        let key = filesystem(&existing).map_err(|e| e.to_string())?;
+        let entry = self.filesystems.entry(key).or_insert((existing, 0));
+        entry.1 += bytes;
+        Ok(())
+    }
+    /// Add the bytes of an output, written first in the staging area of a transaction.
+    /// The commit copies the output if the destination is in other filesystem
+    fn add_output(
+        &mut self,
+        path: &Path,
+        staging: Option<&Path>,
+        bytes: u64,
+    ) -> Result<(), String> {
+        let Some(staging) = staging else {
+            return self.add(path, bytes);
+        };
+        self.add(staging, bytes)?;
+        let filesystem = |path| {
+            existing_ancestor(path)
+                .and_then(|path| filesystem(&path))
+                .map_err(|e| e.to_string())
+        };
+        if filesystem(path)? != filesystem(staging)? {
+            self.add(path, bytes)?;
+        }
+        Ok(())
+    }
+}
+
+/// Nearest ancestor of the path that exists
+fn existing_ancestor(path: &Path) -> io::Result<PathBuf> {
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        env::current_dir()?.join(path)
+    };
+    Ok(path
+        .ancestors()
+        .find(|p| p.exists())
+        .unwrap_or(&path)
+        .to_path_buf())
+}
+
+/// Identifier of the filesystem that contains the path
+#[cfg(unix)]
+fn filesystem(path: &Path) -> io::Result<String> {
+    use std::os::unix::fs::MetadataExt;
+    Ok(path.metadata()?.dev().to_string())
+}
+
+/// Identifier of the filesystem that contains the path
+#[cfg(not(unix))]
+fn filesystem(path: &Path) -> io::Result<String> {
+    let path = path.canonicalize()?;
+    Ok(path
+        .components()
+        .next()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .unwrap_or_default())
+}
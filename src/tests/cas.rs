@@ -1,10 +1,32 @@
-use std::{fs, path::PathBuf};
+use std::{
+    fs,
+    hash::DefaultHasher,
+    io::Read,
+    path::Path,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use sha2::Sha256;
 
 use tempfile::tempdir;
 
-use crate::cas::{Store, default::DefaultStore};
+use crate::{
+    CHUNK_SIZE, Downloader, File,
+    cas::{
+        self, LinkMode, Store,
+        default::{DefaultStore, METADATA, ROOTS},
+        gc::Gc,
+    },
+    hash::{Digest, Hash, Hasher, algorithm::HashAlgorithm},
+    indicator::silent::SilentFactory,
+};
+
+use super::{init_tracing, response, serve};
 
-use super::init_tracing;
+const HELLO: &str = "7509e5bda0c762d2bac7f90d758b5b2263fa01ccbc542ab5e3df163be08e6ca9";
 
 #[test]
 fn write_file() {
@@ -15,10 +37,314 @@ fn write_file() {
     let dst = path.join("file.txt");
     let objects = path.join("objects");
     let store = DefaultStore::new(objects);
-    store
-        .create(CONTENT.to_vec(), PathBuf::from(dst.clone()))
-        .unwrap();
+    store.create(CONTENT.to_vec(), dst.clone()).unwrap();
 
     let read = fs::read_to_string(dst).unwrap();
     assert_eq!(read, String::from_utf8(CONTENT.to_vec()).unwrap());
 }
+
+#[test]
+fn store_records_the_algorithm() {
+    init_tracing();
+    let loc = tempdir().unwrap();
+    let objects = loc.path().join("objects");
+    let store = DefaultStore::new(&objects);
+    let path = store.write(b"hello world!".to_vec()).unwrap();
+    assert_eq!(path, objects.join("75").join(HELLO));
+    let metadata = fs::read_to_string(objects.join(METADATA)).unwrap();
+    assert!(metadata.contains("algorithm=sha256"));
+
+    // Other algorithm is refused until the store is migrated
+    let store = DefaultStore::new_with_algorithm(&objects, HashAlgorithm::Sha512);
+    let err = store.write(b"other".to_vec()).unwrap_err();
+    assert!(err.contains("uses sha256"));
+    assert_eq!(
+        DefaultStore::open(&objects).unwrap().algorithm(),
+        Some(HashAlgorithm::Sha256)
+    );
+}
+
+#[test]
+fn migrate_legacy_store() {
+    init_tracing();
+    let loc = tempdir().unwrap();
+    let objects = loc.path().join("objects");
+    let links = loc.path().join("links");
+    // A store written before the metadata, named with the DefaultHasher
+    let legacy = DefaultHasher::compute(b"hello world!").unwrap();
+    fs::create_dir_all(objects.join(&legacy[0..2])).unwrap();
+    let old = objects.join(&legacy[0..2]).join(&legacy);
+    fs::write(&old, b"hello world!").unwrap();
+    fs::create_dir_all(&links).unwrap();
+    crate::utils::create_symlink(&old, links.join("hello.txt")).unwrap();
+    // A registered root outside of the links folders
+    let root = loc.path().join("root.txt");
+    crate::utils::create_symlink(&old, &root).unwrap();
+
+    assert_eq!(DefaultStore::open(&objects).unwrap().algorithm(), None);
+    let store = DefaultStore::new(&objects);
+    store.register_root(&root).unwrap();
+    assert!(store.write(b"other".to_vec()).is_err());
+
+    let moved = store.migrate(&[&links]).unwrap();
+    assert_eq!(moved.len(), 1);
+    // The same store writes after the migration
+    store.write(b"other".to_vec()).unwrap();
+    assert!(!old.exists());
+    assert_eq!(fs::read_link(links.join("hello.txt")).unwrap(), moved[0].1);
+    assert_eq!(fs::read_link(&root).unwrap(), moved[0].1);
+    // The objects are written through temp files, none is left in the store
+    assert!(fs::read_dir(&objects).unwrap().all(|entry| {
+        let entry = entry.unwrap();
+        entry.file_type().unwrap().is_dir()
+            || !entry.file_name().to_string_lossy().starts_with(".tmp")
+    }));
+    assert_eq!(
+        fs::read_to_string(links.join("hello.txt")).unwrap(),
+        "hello world!"
+    );
+    let store = DefaultStore::open(&objects).unwrap();
+    assert_eq!(store.algorithm(), Some(HashAlgorithm::Sha256));
+    assert!(store.migrate(&[&links]).unwrap().is_empty());
+}
+
+#[test]
+fn store_read_operations() {
+    init_tracing();
+    let loc = tempdir().unwrap();
+    let store = DefaultStore::new(loc.path().join("objects"));
+    let hello = store.digest(b"hello world!");
+    let other = store.digest(b"other");
+    assert!(!store.contains(&hello));
+    assert!(store.open(&hello).is_err());
+
+    let path = store.write(b"hello world!".to_vec()).unwrap();
+    store.write(b"other".to_vec()).unwrap();
+    assert!(store.contains(&hello));
+    assert_eq!(store.path(&hello), Some(path));
+    let mut content = String::new();
+    store
+        .open(&hello)
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    assert_eq!(content, "hello world!");
+
+    let mut digests: Vec<Digest> = store.iter().map(Result::unwrap).collect();
+    digests.sort_by_key(Digest::to_hex);
+    let mut expected = vec![hello.clone(), other.clone()];
+    expected.sort_by_key(Digest::to_hex);
+    assert_eq!(digests, expected);
+
+    assert!(store.remove(&hello).unwrap());
+    assert!(!store.remove(&hello).unwrap());
+    assert!(!store.contains(&hello));
+    assert_eq!(store.iter().count(), 1);
+}
+
+#[test]
+fn store_hit_skips_the_request() {
+    init_tracing();
+    let requests = Arc::new(AtomicUsize::new(0));
+    let counter = requests.clone();
+    let url = serve(2, move |_| {
+        counter.fetch_add(1, Ordering::SeqCst);
+        response("200 OK", &[], b"hello world!")
+    });
+    let loc = tempdir().unwrap();
+    let store = Arc::new(DefaultStore::new(loc.path().join("objects")));
+    let file = |name: &str| {
+        File::new(&format!("{url}/hello.txt"))
+            .with_path(loc.path().join(name))
+            .with_hash(Hash::new::<Sha256>(HELLO))
+            .with_store(store.clone())
+    };
+
+    let downloaded = Downloader::new(SilentFactory::new())
+        .with_file(file("first.txt"))
+        .start()
+        .unwrap();
+    assert!(!downloaded[0].from_store);
+
+    let downloaded = Downloader::new(SilentFactory::new())
+        .with_file(file("second.txt"))
+        .start()
+        .unwrap();
+    assert!(downloaded[0].from_store);
+    assert_eq!(downloaded[0].digests[0].1.to_hex(), HELLO);
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+    assert_eq!(
+        fs::read_to_string(loc.path().join("second.txt")).unwrap(),
+        "hello world!"
+    );
+    assert_eq!(
+        fs::read_link(loc.path().join("second.txt")).unwrap(),
+        fs::read_link(loc.path().join("first.txt")).unwrap()
+    );
+}
+
+#[test]
+fn link_modes() {
+    init_tracing();
+    let loc = tempdir().unwrap();
+    let objects = loc.path().join("store").join("objects");
+    let store = DefaultStore::new(&objects).with_link_mode(LinkMode::RelativeSymlink);
+    let out = loc.path().join("out");
+    store
+        .create(b"hello world!".to_vec(), out.join("relative.txt"))
+        .unwrap();
+    assert_eq!(
+        fs::read_link(out.join("relative.txt")).unwrap(),
+        Path::new("../store/objects/75").join(HELLO)
+    );
+
+    // The relative symlinks are valid after moving the store and the files together
+    let moved = tempdir().unwrap();
+    fs::rename(loc.path().join("store"), moved.path().join("store")).unwrap();
+    fs::rename(&out, moved.path().join("out")).unwrap();
+    assert_eq!(
+        fs::read_to_string(moved.path().join("out/relative.txt")).unwrap(),
+        "hello world!"
+    );
+
+    let store = DefaultStore::new(moved.path().join("store/objects"));
+    for mode in [LinkMode::Hardlink, LinkMode::Reflink, LinkMode::Copy] {
+        let dst = moved.path().join(format!("out/{mode:?}.txt"));
+        store.write(b"hello world!".to_vec()).unwrap();
+        cas::link(
+            &store.path(&store.digest(b"hello world!")).unwrap(),
+            &dst,
+            mode,
+        )
+        .unwrap();
+        assert!(!dst.symlink_metadata().unwrap().is_symlink());
+        assert_eq!(fs::read_to_string(&dst).unwrap(), "hello world!");
+    }
+}
+
+#[test]
+fn file_link_mode() {
+    init_tracing();
+    let url = serve(1, |_| response("200 OK", &[], b"hello world!"));
+    let loc = tempdir().unwrap();
+    let store = Arc::new(DefaultStore::new(loc.path().join("objects")));
+    let dst = loc.path().join("hello.txt");
+    Downloader::new(SilentFactory::new())
+        .with_file(
+            File::new(&format!("{url}/hello.txt"))
+                .with_path(&dst)
+                .with_store(store.clone())
+                .with_link_mode(LinkMode::Copy),
+        )
+        .start()
+        .unwrap();
+    assert!(!dst.symlink_metadata().unwrap().is_symlink());
+    assert_eq!(fs::read_to_string(&dst).unwrap(), "hello world!");
+    assert!(store.contains(&store.digest(b"hello world!")));
+    // The downloaded file is a root of the object
+    let roots = fs::read_to_string(loc.path().join("objects").join(ROOTS)).unwrap();
+    assert_eq!(roots.trim(), dst.to_string_lossy());
+}
+
+#[test]
+fn streaming_writes() {
+    init_tracing();
+    let loc = tempdir().unwrap();
+    let objects = loc.path().join("objects");
+    let store = DefaultStore::new(&objects);
+    let big = vec![3u8; 5 * CHUNK_SIZE + 7];
+    let path = store.write_reader(&mut big.as_slice()).unwrap();
+    assert_eq!(path, store.path(&store.digest(&big)).unwrap());
+    assert_eq!(fs::read(&path).unwrap(), big);
+
+    // An existing file is adopted with a rename
+    let file = loc.path().join("hello.txt");
+    fs::write(&file, b"hello world!").unwrap();
+    let path = store.adopt(&file).unwrap();
+    assert!(!file.exists());
+    assert_eq!(path, objects.join("75").join(HELLO));
+    // The same content is deduplicated
+    fs::write(&file, b"hello world!").unwrap();
+    assert_eq!(store.adopt(&file).unwrap(), path);
+    assert!(!file.exists());
+    assert_eq!(store.iter().count(), 2);
+
+    // No temp file is left in the store
+    let leftovers = fs::read_dir(&objects)
+        .unwrap()
+        .filter(|entry| entry.as_ref().unwrap().file_type().unwrap().is_file())
+        .count();
+    assert_eq!(leftovers, 1);
+
+    // A store without a usable directory fails the download
+    let url = serve(1, |_| response("200 OK", &[], b"hello world!"));
+    let blocked = loc.path().join("blocked");
+    fs::write(&blocked, b"").unwrap();
+    let result = Downloader::new(SilentFactory::new())
+        .with_file(
+            File::new(&url)
+                .with_path(loc.path().join("blocked.txt"))
+                .with_store(Arc::new(DefaultStore::new(&blocked))),
+        )
+        .start();
+    assert!(result.is_err());
+}
+
+#[test]
+fn garbage_collection() {
+    init_tracing();
+    let loc = tempdir().unwrap();
+    let out = loc.path().join("out");
+    let store = DefaultStore::new(loc.path().join("objects"));
+    store
+        .create(b"kept".to_vec(), out.join("kept.txt"))
+        .unwrap();
+    store
+        .create(b"unlinked".to_vec(), out.join("unlinked.txt"))
+        .unwrap();
+    store
+        .create(b"hardlink".to_vec(), out.join("hardlink.txt"))
+        .unwrap();
+    // A path is only registered once, from any store of the same directory
+    DefaultStore::new(loc.path().join("objects"))
+        .register_root(&out.join("kept.txt"))
+        .unwrap();
+    let roots = fs::read_to_string(loc.path().join("objects").join(ROOTS)).unwrap();
+    assert_eq!(roots.lines().count(), 3);
+    // A symlink created without the registry
+    let scanned = store.write(b"scanned".to_vec()).unwrap();
+    crate::utils::create_symlink(&scanned, loc.path().join("scanned.txt")).unwrap();
+    let orphan = store.write(b"orphan".to_vec()).unwrap();
+
+    fs::remove_file(out.join("unlinked.txt")).unwrap();
+    let hardlink = store.path(&store.digest(b"hardlink")).unwrap();
+    cas::link(&hardlink, &out.join("hardlink.txt"), LinkMode::Hardlink).unwrap();
+
+    let report = store.gc(&Gc::new().with_dry_run(true)).unwrap();
+    assert_eq!(report.removed.len(), 3);
+    assert_eq!(
+        report.reclaimed,
+        ("unlinked".len() + "scanned".len() + "orphan".len()) as u64
+    );
+    assert_eq!(report.kept, 2);
+    assert!(orphan.exists());
+
+    let report = store.gc(&Gc::new().with_scan_dir(loc.path())).unwrap();
+    assert_eq!(report.removed.len(), 2);
+    assert_eq!(
+        report.reclaimed,
+        "unlinked".len() as u64 + "orphan".len() as u64
+    );
+    assert_eq!(report.kept, 3);
+    assert_eq!(report.pruned_roots, 1);
+    assert!(!orphan.exists());
+    assert!(scanned.exists());
+    assert_eq!(fs::read_to_string(out.join("kept.txt")).unwrap(), "kept");
+    assert_eq!(
+        fs::read_to_string(loc.path().join("objects").join(ROOTS))
+            .unwrap()
+            .lines()
+            .count(),
+        2
+    );
+}
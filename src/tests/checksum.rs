@@ -0,0 +1,90 @@
+use std::fs;
+
+use tempfile::tempdir;
+
+use crate::{
+    Downloader, File,
+    checksum::{find, parse},
+    hash::algorithm::HashAlgorithm,
+    indicator::silent::SilentFactory,
+};
+
+use super::{init_tracing, response, serve};
+
+const HELLO: &str = "7509e5bda0c762d2bac7f90d758b5b2263fa01ccbc542ab5e3df163be08e6ca9";
+const WORLD: &str = "1b8ce16d3890dbd81a225eeb05cf83d56bd13c157afce1c4aa56c17068e658b1";
+
+#[test]
+fn parse_checksum_files() {
+    // GNU coreutils, text and binary mode
+    let content = format!(
+        "# comment\n{HELLO}  hello.txt\n{}  *dist/world.txt\n",
+        WORLD.to_uppercase()
+    );
+    let entries = parse(&content, "https://example.com/v1/SHA256SUMS");
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[1].name, "dist/world.txt");
+    assert_eq!(entries[1].digest, WORLD);
+    assert_eq!(entries[1].algorithm, Some(HashAlgorithm::Sha256));
+    assert_eq!(find(&entries, &["world.txt"]), Some(&entries[1]));
+    assert_eq!(find(&entries, &["other.txt"]), None);
+
+    // BSD
+    let content = format!(
+        "SHA256 (hello.txt) = {HELLO}\nMD5 (hello.txt) = fc3ff98e8c6a0d3087d515c0473f8677\n"
+    );
+    let entries = parse(&content, "CHECKSUMS");
+    assert_eq!(entries[0].algorithm, Some(HashAlgorithm::Sha256));
+    assert_eq!(entries[1].algorithm, Some(HashAlgorithm::Md5));
+
+    // Single digest, the algorithm is inferred from the length
+    let entries = parse(&format!("{HELLO}\n"), "hello.txt.digest");
+    assert_eq!(entries[0].algorithm, Some(HashAlgorithm::Sha256));
+    assert_eq!(find(&entries, &["hello.txt"]), Some(&entries[0]));
+}
+
+#[test]
+fn download_with_checksum_file() {
+    init_tracing();
+    let url = serve(5, |head| {
+        if head.starts_with("GET /SHA256SUMS") {
+            let sums = format!("{HELLO}  hello.txt\n{WORLD}  world.txt\n");
+            response("200 OK", &[], sums.as_bytes())
+        } else if head.starts_with("GET /hello.txt") {
+            response("200 OK", &[], b"hello world!")
+        } else {
+            // The content of world.txt is wrong
+            response("200 OK", &[], b"hello world!")
+        }
+    });
+    let dir = tempdir().unwrap();
+    let downloaded = Downloader::new(SilentFactory::new())
+        .with_checksum_url(&format!("{url}/SHA256SUMS"))
+        .with_file(File::new(&format!("{url}/hello.txt")).with_path(dir.path().join("hello.txt")))
+        .start()
+        .unwrap();
+    assert_eq!(downloaded[0].digests[0].1.to_hex(), HELLO);
+
+    let err = Downloader::new(SilentFactory::new())
+        .with_file(
+            File::new(&format!("{url}/world.txt"))
+                .with_path(dir.path().join("world.txt"))
+                .with_checksum_url(&format!("{url}/SHA256SUMS")),
+        )
+        .start()
+        .unwrap_err();
+    assert_eq!(err.mismatches().next().unwrap().expected, WORLD);
+    assert!(!dir.path().join("world.txt").exists());
+
+    // The checksum file doesn't have an entry of the file
+    let err = Downloader::new(SilentFactory::new())
+        .with_file(
+            File::new(&format!("{url}/missing.txt"))
+                .with_path(dir.path().join("missing.txt"))
+                .with_checksum_url(&format!("{url}/SHA256SUMS")),
+        )
+        .start()
+        .unwrap_err();
+    assert!(err.to_string().contains("No checksum of missing.txt"));
+    assert!(fs::read_dir(dir.path()).unwrap().count() == 1);
+}
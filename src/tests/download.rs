@@ -0,0 +1,453 @@
+use std::{
+    fs,
+    sync::{Arc, Mutex, mpsc},
+    time::Duration,
+};
+
+use tempfile::tempdir;
+
+use sha1::Sha1;
+
+use crate::{
+    Conflict, Downloader, ErrorMode, File, cache::HttpCache, events::DownloadEvent, hash::Hash,
+    hash::algorithm::HashAlgorithm, indicator::silent::SilentFactory, redirect::RedirectPolicy,
+    sink::Sink,
+};
+
+use super::{init_tracing, response, serve};
+
+#[test]
+fn conditional_request_reuses_cache() {
+    init_tracing();
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let log = requests.clone();
+    let url = serve(2, move |head| {
+        log.lock().unwrap().push(head.to_string());
+        if head.contains("If-None-Match: \"v1\"") {
+            response("304 Not Modified", &[("ETag", "\"v1\"")], b"")
+        } else {
+            response("200 OK", &[("ETag", "\"v1\"")], b"Hello World!")
+        }
+    });
+    let dir = tempdir().unwrap();
+    let dst = dir.path().join("index.json");
+    let cache = Arc::new(HttpCache::new(dir.path().join("cache")));
+
+    for _ in 0..2 {
+        Downloader::new(SilentFactory::new())
+            .with_cache(cache.clone())
+            .with_file(File::new(&format!("{url}/index.json")).with_path(&dst))
+            .start()
+            .unwrap();
+        assert_eq!(fs::read_to_string(&dst).unwrap(), "Hello World!");
+        fs::remove_file(&dst).unwrap();
+    }
+
+    let requests = requests.lock().unwrap();
+    assert!(!requests[0].contains("If-None-Match"));
+    assert!(requests[1].contains("If-None-Match: \"v1\""));
+}
+
+#[test]
+fn download_into_memory() {
+    init_tracing();
+    let url = serve(1, |_| response("200 OK", &[], b"hello world!"));
+    let downloaded = Downloader::new(SilentFactory::new())
+        .with_file(
+            File::new(&format!("{url}/index.json"))
+                .with_sink(Sink::Memory)
+                .with_hash(Hash::new::<Sha1>(
+                    "430ce34d020724ed75a196dfc2ad67c77772d169",
+                )),
+        )
+        .start()
+        .unwrap();
+    assert_eq!(downloaded.len(), 1);
+    assert_eq!(downloaded[0].bytes.as_deref(), Some(&b"hello world!"[..]));
+}
+
+#[test]
+fn download_into_channel() {
+    init_tracing();
+    let url = serve(2, |_| response("200 OK", &[], b"hello world!"));
+    let dir = tempdir().unwrap();
+    let (sender, receiver) = mpsc::channel();
+    // The stream is hashed on the fly, without the batch cache or a buffer of the declared size
+    let downloaded = Downloader::new(SilentFactory::new())
+        .with_cache(Arc::new(HttpCache::new(dir.path().join("cache"))))
+        .with_file(
+            File::new(&url)
+                .with_sink(Sink::Channel(sender))
+                .with_size(50 << 30)
+                .with_hash(Hash::new::<Sha1>(
+                    "430ce34d020724ed75a196dfc2ad67c77772d169",
+                )),
+        )
+        .start()
+        .unwrap();
+    assert_eq!(downloaded[0].bytes, None);
+    assert_eq!(downloaded[0].digests.len(), 1);
+    assert_eq!(
+        receiver.iter().flatten().collect::<Vec<u8>>(),
+        b"hello world!"
+    );
+    assert!(!dir.path().join("cache").exists());
+
+    let err = Downloader::new(SilentFactory::new())
+        .with_file(
+            File::new(&url)
+                .with_sink(Sink::writer(std::io::sink()))
+                .with_hash(Hash::new::<Sha1>(&"0".repeat(40))),
+        )
+        .start()
+        .unwrap_err();
+    assert!(err.to_string().contains("mismatch"));
+}
+
+#[test]
+fn continue_returns_aggregate_error() {
+    init_tracing();
+    let url = serve(2, |head| {
+        if head.starts_with("GET /missing") {
+            response("404 Not Found", &[], b"")
+        } else {
+            response("200 OK", &[], b"world hello!")
+        }
+    });
+    let dir = tempdir().unwrap();
+    let bad = dir.path().join("bad.txt");
+    let missing = dir.path().join("missing.txt");
+    let error = Downloader::new(SilentFactory::new())
+        .with_error_mode(ErrorMode::Continue)
+        .with_files(vec![
+            File::new(&format!("{url}/bad.txt"))
+                .with_path(&bad)
+                .with_hash(Hash::new::<Sha1>(
+                    "430ce34d020724ed75a196dfc2ad67c77772d169",
+                )),
+            File::new(&format!("{url}/missing")).with_path(&missing),
+        ])
+        .start()
+        .unwrap_err()
+        .to_string();
+
+    assert!(error.starts_with("2 of 2 files failed"));
+    // The partial outputs are removed
+    assert!(!bad.exists());
+    assert!(!missing.exists());
+}
+
+#[test]
+fn fail_fast_cancels_pending() {
+    init_tracing();
+    let error = Downloader::new(SilentFactory::new())
+        .with_error_mode(ErrorMode::FailFast)
+        .with_max_current_downloads(1)
+        .with_files(vec![
+            File::new("http://127.0.0.1:1/a"),
+            File::new("http://127.0.0.1:1/b"),
+        ])
+        .start()
+        .unwrap_err()
+        .to_string();
+
+    assert!(error.starts_with("1 of 2 files failed"));
+    assert!(error.contains("Cancelled"));
+}
+
+#[test]
+fn download_into_dir_renames_conflicts() {
+    init_tracing();
+    let url = serve(2, |_| {
+        response(
+            "200 OK",
+            &[(
+                "Content-Disposition",
+                "attachment; filename=\"asset.tar.gz\"",
+            )],
+            b"hello world!",
+        )
+    });
+    let dir = tempdir().unwrap();
+    let mut downloaded = Downloader::new(SilentFactory::new())
+        .with_conflict(Conflict::Rename)
+        .with_files(vec![
+            File::new(&format!("{url}/download?id=1")).with_dir(dir.path()),
+            File::new(&format!("{url}/download?id=2")).with_dir(dir.path()),
+        ])
+        .start()
+        .unwrap();
+
+    downloaded.sort_by(|a, b| a.path.cmp(&b.path));
+    assert_eq!(downloaded[0].path, dir.path().join("asset-1.tar.gz"));
+    assert_eq!(downloaded[1].path, dir.path().join("asset.tar.gz"));
+    assert_eq!(
+        fs::read_to_string(dir.path().join("asset-1.tar.gz")).unwrap(),
+        "hello world!"
+    );
+}
+
+#[test]
+fn download_into_dir_renames_conflicts_in_transaction() {
+    init_tracing();
+    let url = serve(2, |head| {
+        let body: &[u8] = match head.contains("id=1") {
+            true => b"first",
+            false => b"second",
+        };
+        response(
+            "200 OK",
+            &[("Content-Disposition", "attachment; filename=\"asset.bin\"")],
+            body,
+        )
+    });
+    let dir = tempdir().unwrap();
+    let mut downloaded = Downloader::new(SilentFactory::new())
+        .with_conflict(Conflict::Rename)
+        .with_transaction(true)
+        .with_files(vec![
+            File::new(&format!("{url}/download?id=1")).with_dir(dir.path()),
+            File::new(&format!("{url}/download?id=2")).with_dir(dir.path()),
+        ])
+        .start()
+        .unwrap();
+
+    // The names are claimed in the final directory, no file overwrites the other
+    downloaded.sort_by(|a, b| a.path.cmp(&b.path));
+    assert_eq!(downloaded[0].path, dir.path().join("asset-1.bin"));
+    assert_eq!(downloaded[1].path, dir.path().join("asset.bin"));
+    let mut contents: Vec<String> = downloaded
+        .iter()
+        .map(|d| fs::read_to_string(&d.path).unwrap())
+        .collect();
+    contents.sort();
+    assert_eq!(contents, ["first", "second"]);
+}
+
+#[test]
+fn redirects_are_recorded() {
+    init_tracing();
+    let url = serve(3, |head| {
+        if head.starts_with("GET /final") {
+            response("200 OK", &[], b"hello world!")
+        } else if head.starts_with("GET /middle") {
+            response("302 Found", &[("Location", "/final")], b"")
+        } else {
+            response("301 Moved Permanently", &[("Location", "middle")], b"")
+        }
+    });
+    let downloaded = Downloader::new(SilentFactory::new())
+        .with_file(File::new(&format!("{url}/start")).with_sink(Sink::Memory))
+        .start()
+        .unwrap();
+    assert_eq!(downloaded[0].final_url, format!("{url}/final"));
+    assert_eq!(
+        downloaded[0].redirects,
+        vec![format!("{url}/start"), format!("{url}/middle")]
+    );
+    assert_eq!(downloaded[0].bytes.as_deref(), Some(&b"hello world!"[..]));
+}
+
+#[test]
+fn redirect_policy_refuses_hops() {
+    init_tracing();
+    let url = serve(2, |_| {
+        response("302 Found", &[("Location", "http://localhost/file")], b"")
+    });
+    let err = Downloader::new(SilentFactory::new())
+        .with_redirect_policy(RedirectPolicy::new().with_denied_host("localhost"))
+        .with_file(File::new(&url).with_sink(Sink::Memory))
+        .start()
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("Refused denied host: localhost"));
+
+    let err = Downloader::new(SilentFactory::new())
+        .with_redirect_policy(RedirectPolicy::new().with_max_redirects(0))
+        .with_file(File::new(&url).with_sink(Sink::Memory))
+        .start()
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("Too many redirects"));
+
+    let err = Downloader::new(SilentFactory::new())
+        .with_redirect_policy(RedirectPolicy::new().with_https_only(true))
+        .with_file(File::new(&url).with_sink(Sink::Memory))
+        .start()
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("Refused non https url"));
+}
+
+#[test]
+fn agent_following_redirects() {
+    init_tracing();
+    let url = serve(4, |head| {
+        if head.starts_with("GET /final") {
+            response("200 OK", &[], b"hello world!")
+        } else {
+            response("302 Found", &[("Location", "/final")], b"")
+        }
+    });
+    // Accepted with the default policy
+    let downloaded = Downloader::new(SilentFactory::new())
+        .with_ureq_agent(ureq::agent())
+        .with_file(File::new(&format!("{url}/start")).with_sink(Sink::Memory))
+        .start()
+        .unwrap();
+    assert_eq!(downloaded[0].final_url, format!("{url}/final"));
+    assert_eq!(downloaded[0].redirects, vec![format!("{url}/start")]);
+    assert_eq!(downloaded[0].bytes.as_deref(), Some(&b"hello world!"[..]));
+
+    // The hops can't be checked against a restrictive policy
+    let err = Downloader::new(SilentFactory::new())
+        .with_ureq_agent(ureq::agent())
+        .with_redirect_policy(RedirectPolicy::new().with_allowed_host("127.0.0.1"))
+        .with_file(File::new(&format!("{url}/start")).with_sink(Sink::Memory))
+        .start()
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("redirects(0)"));
+}
+
+#[test]
+fn events_report_the_batch() {
+    init_tracing();
+    let url = serve(1, |_| response("200 OK", &[], b"hello world!"));
+    let mut downloader = Downloader::new(SilentFactory::new())
+        .with_file(File::new(&format!("{url}/index.json")).with_sink(Sink::Memory))
+        .with_file(File::new(&format!("{url}/missing.json")).with_sink(Sink::Memory));
+    let events = downloader.events();
+    // The second file fails to connect, the server only accepts one connection
+    let _ = downloader.with_max_current_downloads(1).start();
+
+    let events: Vec<DownloadEvent> = events.iter().collect();
+    assert!(matches!(events[0], DownloadEvent::Queued { id: 0, .. }));
+    assert!(matches!(events[1], DownloadEvent::Queued { id: 1, .. }));
+    assert!(events.iter().any(|event| matches!(event,
+        DownloadEvent::Started { id: 0, name } if name == "index.json")));
+    assert!(
+        events
+            .iter()
+            .any(|event| matches!(event, DownloadEvent::Length { id: 0, bytes: 12 }))
+    );
+    assert!(
+        events
+            .iter()
+            .any(|event| matches!(event, DownloadEvent::Progress { id: 0, bytes: 12 }))
+    );
+    assert!(events.iter().any(|event| matches!(event,
+        DownloadEvent::Finished { id: 0, result: Ok(file) } if file.bytes.as_deref() == Some(&b"hello world!"[..]))));
+    assert!(events.iter().any(|event| matches!(
+        event,
+        DownloadEvent::Finished {
+            id: 1,
+            result: Err(_)
+        }
+    )));
+}
+
+#[test]
+fn progress_is_coalesced() {
+    init_tracing();
+    let url = serve(1, |_| response("200 OK", &[], &[7u8; 100 * 1024]));
+    let mut downloader = Downloader::new(SilentFactory::new())
+        .with_buffer_size(1024)
+        .with_progress_interval(Duration::from_secs(60), 32 * 1024)
+        .with_file(File::new(&url).with_sink(Sink::Memory));
+    let events = downloader.events();
+    let downloaded = downloader.start().unwrap();
+    assert_eq!(downloaded[0].bytes.as_ref().unwrap().len(), 100 * 1024);
+
+    let progress: Vec<u64> = events
+        .iter()
+        .filter_map(|event| match event {
+            DownloadEvent::Progress { bytes, .. } => Some(bytes),
+            _ => None,
+        })
+        .collect();
+    // Three updates every 32KB and the last position
+    assert_eq!(progress.len(), 4);
+    assert_eq!(progress.last(), Some(&(100 * 1024)));
+}
+
+#[test]
+fn multiple_hashes_are_checked() {
+    init_tracing();
+    let url = serve(2, |_| response("200 OK", &[], b"hello world!"));
+    let dir = tempdir().unwrap();
+    let md5 = Hash::parse("md5:fc3ff98e8c6a0d3087d515c0473f8677").unwrap();
+    let sha256 =
+        Hash::parse("sha256:7509e5bda0c762d2bac7f90d758b5b2263fa01ccbc542ab5e3df163be08e6ca9")
+            .unwrap();
+    let downloaded = Downloader::new(SilentFactory::new())
+        .with_file(
+            File::new(&url)
+                .with_path(dir.path().join("file"))
+                .with_hashes(vec![md5.clone(), sha256]),
+        )
+        .start()
+        .unwrap();
+    let digests: Vec<(String, String)> = downloaded[0]
+        .digests
+        .iter()
+        .map(|(algorithm, digest)| (algorithm.unwrap().to_string(), digest.to_hex()))
+        .collect();
+    assert_eq!(
+        digests,
+        vec![
+            (
+                "md5".to_string(),
+                "fc3ff98e8c6a0d3087d515c0473f8677".to_string()
+            ),
+            (
+                "sha256".to_string(),
+                "7509e5bda0c762d2bac7f90d758b5b2263fa01ccbc542ab5e3df163be08e6ca9".to_string()
+            ),
+        ]
+    );
+
+    // A single mismatch fails the download
+    let wrong = Hash::parse("sha1:0000000000000000000000000000000000000000").unwrap();
+    let err = Downloader::new(SilentFactory::new())
+        .with_file(
+            File::new(&url)
+                .with_path(dir.path().join("other"))
+                .with_hash(md5)
+                .with_hash(wrong),
+        )
+        .start()
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("Hash mismatch"));
+    assert!(!dir.path().join("other").exists());
+}
+
+#[test]
+fn hash_mismatch_is_quarantined() {
+    init_tracing();
+    let url = serve(1, |_| response("200 OK", &[], b"world hello!"));
+    let dir = tempdir().unwrap();
+    let dst = dir.path().join("file.txt");
+    let err = Downloader::new(SilentFactory::new())
+        .with_quarantine_dir(dir.path().join("quarantine"))
+        .with_file(File::new(&url).with_path(&dst).with_hash(Hash::new::<Sha1>(
+            "430CE34D020724ED75A196DFC2AD67C77772D169",
+        )))
+        .start()
+        .unwrap_err();
+
+    let mismatch = err.mismatches().next().unwrap();
+    assert_eq!(mismatch.url, url);
+    assert_eq!(mismatch.algorithm, Some(HashAlgorithm::Sha1));
+    assert_eq!(
+        mismatch.expected,
+        "430ce34d020724ed75a196dfc2ad67c77772d169"
+    );
+    assert_eq!(mismatch.actual, "53d4dabf9177c1af3dcec510fc0e6c02ac8cafda");
+    assert_eq!(mismatch.size, 12);
+    let quarantined = mismatch.quarantined.as_ref().unwrap();
+    assert_eq!(quarantined, &dir.path().join("quarantine/file.txt"));
+    assert_eq!(fs::read(quarantined).unwrap(), b"world hello!");
+    assert!(!dst.exists());
+}
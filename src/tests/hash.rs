@@ -1,10 +1,11 @@
-use std::io::Write;
+use std::{hash::DefaultHasher, io::Write};
 
 use sha1::Sha1;
+use sha2::Sha256;
 use tempfile::NamedTempFile;
 
 use crate::{
-    hash::{Hash, md5::Md5},
+    hash::{Hash, Hasher, algorithm::HashAlgorithm, md5::Md5},
     tests::init_tracing,
 };
 
@@ -20,7 +21,7 @@ fn sha_check_bytes_test() {
 fn sha_check_file_test() {
     init_tracing();
     let mut file = NamedTempFile::new().unwrap();
-    file.write(b"This is a example file\n").unwrap();
+    file.write_all(b"This is a example file\n").unwrap();
 
     let hash = Hash::new::<Sha1>("59773469265971aa2b3d70c939f7179ffcd95014");
     assert_eq!(hash.check_file(file).unwrap(), Some(()));
@@ -38,8 +39,165 @@ fn md5_check_bytes_test() {
 fn md5_check_file_test() {
     init_tracing();
     let mut file = NamedTempFile::new().unwrap();
-    file.write(b"This is a example file\n").unwrap();
+    file.write_all(b"This is a example file\n").unwrap();
 
     let hash = Hash::new::<Md5>("2bdd613e96bcbbf006a7b9909979923f");
     assert_eq!(hash.check_file(file).unwrap(), Some(()));
 }
+
+#[test]
+fn incremental_hash_matches_compute() {
+    init_tracing();
+    let content = b"hello world!".repeat(10_000);
+    fn chunked<T: Hasher + 'static>(content: &[u8]) -> String {
+        let mut hasher: Box<dyn Hasher> = Box::new(T::new());
+        for chunk in content.chunks(7) {
+            hasher.update(chunk);
+        }
+        hasher.finalize().to_hex()
+    }
+    assert_eq!(
+        chunked::<Sha256>(&content),
+        Sha256::compute(&content).unwrap()
+    );
+    assert_eq!(chunked::<Md5>(&content), Md5::compute(&content).unwrap());
+    // The store paths depend on the default hasher
+    assert_eq!(
+        chunked::<DefaultHasher>(&content),
+        DefaultHasher::compute(&content).unwrap()
+    );
+}
+
+#[test]
+fn check_reader_test() {
+    init_tracing();
+    let hash = Hash::new::<Sha1>("430ce34d020724ed75a196dfc2ad67c77772d169");
+    assert_eq!(hash.check_reader(&b"hello world!"[..]).unwrap(), Some(()));
+    assert_eq!(hash.check_reader(&b"world hello!"[..]).unwrap(), None);
+}
+
+#[test]
+fn sha2_vectors() {
+    use sha2::{Sha384, Sha512};
+    assert_eq!(
+        Sha384::compute(b"hello world!").unwrap(),
+        "d33d40f7010ce34aa86efd353630309ed5c3d7ffac66d988825cf699f4803ccdf3f033230612f0945332fb580d8af805"
+    );
+    assert_eq!(
+        Sha512::compute(b"hello world!").unwrap(),
+        "db9b1cd3262dee37756a09b9064973589847caa8e53d31a9d142ea2701b1b28abd97838bb9a27068ba305dc8d04a45a1fcf079de54d607666996b3cc54f6b67c"
+    );
+}
+
+#[cfg(feature = "sha3")]
+#[test]
+fn sha3_vectors() {
+    use sha3::{Sha3_224, Sha3_256, Sha3_384, Sha3_512};
+    assert_eq!(
+        Sha3_224::compute(b"hello world!").unwrap(),
+        "f8551fe638d604e2cac84f39b5c38f400d161245a16359b59a57286c"
+    );
+    assert_eq!(
+        Sha3_256::compute(b"hello world!").unwrap(),
+        "9c24b06143c07224c897bac972e6e92b46cf18063f1a469ebe2f7a0966306105"
+    );
+    assert_eq!(
+        Sha3_384::compute(b"hello world!").unwrap(),
+        "64bdfdfda3956f86992c4a0fc9a4f31e9d11c43abb463365a57d360261012e955f078601448a62c38d1528ca068049ff"
+    );
+    assert_eq!(
+        Sha3_512::compute(b"hello world!").unwrap(),
+        "5aadcaf394961eecc2f4e65c2d82ff7cf0f6fa4574f351d0053574886ac77c961958cef64bc2bb483b4e7430964b55893a7c28a5c6efab7e24e2b7994bba5eb9"
+    );
+}
+
+#[cfg(feature = "blake2")]
+#[test]
+fn blake2_vectors() {
+    use blake2::{Blake2b512, Blake2s256};
+    assert_eq!(
+        Blake2b512::compute(b"hello world!").unwrap(),
+        "fa02d55d26bc5cda1e2d67fb7424f6132c58fed81a52816342795de54d3b2d8b91749f267d2491ed05ca0cbbd0e641cc1758b92e99eb1d8771060ebacbc83c25"
+    );
+    assert_eq!(
+        Blake2s256::compute(b"hello world!").unwrap(),
+        "6b07ae5278b0eb87df625c2cc592988ecfe4d70fa43867ccf23c0b46bf694479"
+    );
+}
+
+#[cfg(feature = "blake3")]
+#[test]
+fn blake3_vectors() {
+    use crate::hash::blake3::Blake3;
+    assert_eq!(
+        Blake3::compute(b"").unwrap(),
+        "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"
+    );
+    assert_eq!(
+        Blake3::compute(b"abc").unwrap(),
+        "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+    );
+}
+
+#[cfg(feature = "crc32")]
+#[test]
+fn crc32_vectors() {
+    use crate::hash::crc32::Crc32;
+    assert_eq!(Crc32::compute(b"123456789").unwrap(), "cbf43926");
+    assert_eq!(Crc32::compute(b"hello world!").unwrap(), "03b4c26d");
+}
+
+#[cfg(feature = "xxhash")]
+#[test]
+fn xxhash_vectors() {
+    use crate::hash::xxhash::{Xxh3, Xxh64, Xxh128};
+    assert_eq!(Xxh64::compute(b"").unwrap(), "ef46db3751d8e999");
+    assert_eq!(Xxh3::compute(b"").unwrap(), "2d06800538d394c2");
+    assert_eq!(
+        Xxh128::compute(b"").unwrap(),
+        "99aa06d3014798d86001c324468d497f"
+    );
+}
+
+#[test]
+fn parse_algorithms() {
+    init_tracing();
+    assert_eq!("SHA-256".parse(), Ok(HashAlgorithm::Sha256));
+    assert_eq!("sha512_256".parse(), Ok(HashAlgorithm::Sha512_256));
+    assert_eq!("md5".parse(), Ok(HashAlgorithm::Md5));
+    assert!("sha0".parse::<HashAlgorithm>().is_err());
+    for algorithm in HashAlgorithm::ALL {
+        assert_eq!(algorithm.name().parse(), Ok(*algorithm));
+    }
+    assert_eq!(
+        Hash::new::<Sha256>("00").algorithm(),
+        Some(HashAlgorithm::Sha256)
+    );
+}
+
+#[test]
+fn parse_hashes() {
+    init_tracing();
+    let content = b"hello world!".to_vec();
+    // Prefixed and OCI digests, the hex case is ignored
+    let hash = Hash::parse("sha1:430CE34D020724ED75A196DFC2AD67C77772D169").unwrap();
+    assert_eq!(hash.check_bytes(content.clone()), Some(()));
+    let hash: Hash = "sha256:7509e5bda0c762d2bac7f90d758b5b2263fa01ccbc542ab5e3df163be08e6ca9"
+        .parse()
+        .unwrap();
+    assert_eq!(hash.algorithm(), Some(HashAlgorithm::Sha256));
+    assert_eq!(hash.check_bytes(content.clone()), Some(()));
+    // Subresource Integrity
+    let hash = Hash::parse(
+        "sha384-0z1A9wEM40qobv01NjAwntXD1/+sZtmIglz2mfSAPM3z8DMjBhLwlFMy+1gNivgF?ct=text",
+    )
+    .unwrap();
+    assert_eq!(hash.check_bytes(content.clone()), Some(()));
+    // Base64 digests
+    let hash = Hash::new::<Sha1>("QwzjTQIHJO11oZbfwq1nx3dy0Wk=");
+    assert_eq!(hash.check_bytes(content.clone()), Some(()));
+
+    assert!(Hash::parse("430ce34d020724ed75a196dfc2ad67c77772d169").is_err());
+    assert!(Hash::parse("sha1:430ce34d").is_err());
+    assert!(Hash::parse("sha0:430ce34d020724ed75a196dfc2ad67c77772d169").is_err());
+}
@@ -1,4 +1,9 @@
-use std::sync::Once;
+use std::{
+    io::{Read, Write},
+    net::TcpListener,
+    sync::Once,
+    thread,
+};
 use tracing::Level;
 use tracing_subscriber::FmtSubscriber;
 
@@ -8,9 +13,33 @@ mod hash;
 #[cfg(test)]
 mod cas;
 
+#[cfg(test)]
+mod checksum;
+
 #[cfg(test)]
 mod compress;
 
+#[cfg(test)]
+mod download;
+
+#[cfg(test)]
+mod naming;
+
+#[cfg(test)]
+mod plan;
+
+#[cfg(all(test, any(feature = "minisign", feature = "ed25519")))]
+mod signature;
+
+#[cfg(test)]
+mod transaction;
+
+#[cfg(test)]
+mod tree;
+
+#[cfg(test)]
+mod verify;
+
 static INIT: Once = Once::new();
 
 fn init_tracing() {
@@ -24,3 +53,50 @@ fn init_tracing() {
             .expect("Failed to set tracing subscriber");
     });
 }
+
+/// Serve http responses in a local port, the handler receives the request head
+fn serve<F>(connections: usize, handler: F) -> String
+where
+    F: Fn(&str) -> Vec<u8> + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        for stream in listener.incoming().take(connections) {
+            let mut stream = stream.unwrap();
+            let mut head = Vec::new();
+            let mut byte = [0u8; 1];
+            while !head.ends_with(b"\r\n\r\n") && stream.read(&mut byte).unwrap() == 1 {
+                head.push(byte[0]);
+            }
+            let response = handler(&String::from_utf8_lossy(&head));
+            stream.write_all(&response).unwrap();
+        }
+    });
+    format!("http://{addr}")
+}
+
+/// Build a raw http response
+fn response(status: &str, headers: &[(&str, &str)], body: &[u8]) -> Vec<u8> {
+    let mut response = format!("HTTP/1.1 {status}\r\nConnection: close\r\n");
+    for (name, value) in headers {
+        response.push_str(&format!("{name}: {value}\r\n"));
+    }
+    response.push_str(&format!("Content-Length: {}\r\n\r\n", body.len()));
+    let mut response = response.into_bytes();
+    response.extend_from_slice(body);
+    response
+}
+
+/// Create a tar archive with a `hello.txt` file
+fn tar() -> Vec<u8> {
+    let mut builder = tar::Builder::new(Vec::new());
+    let mut header = tar::Header::new_gnu();
+    header.set_size(13);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder
+        .append_data(&mut header, "hello.txt", &b"Hello, world!"[..])
+        .unwrap();
+    builder.into_inner().unwrap()
+}
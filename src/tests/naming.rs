@@ -0,0 +1,40 @@
+use crate::naming::resolve;
+
+#[test]
+fn resolve_file_names() {
+    const URL: &str = "https://example.com/releases/download/v1/tool%20v1.tar.gz?token=1";
+    assert_eq!(
+        resolve(Some("attachment; filename=\"asset.zip\""), URL, URL),
+        "asset.zip"
+    );
+    assert_eq!(
+        resolve(
+            Some("attachment; filename=\"plain.zip\"; filename*=UTF-8''%C3%B1and%C3%BA.zip"),
+            URL,
+            URL
+        ),
+        "ñandú.zip"
+    );
+    assert_eq!(resolve(None, URL, URL), "tool v1.tar.gz");
+    assert_eq!(
+        resolve(None, "https://cdn.example.com/final.bin", URL),
+        "final.bin"
+    );
+    // Unsafe names
+    assert_eq!(
+        resolve(Some("attachment; filename=\"../../etc/passwd\""), URL, URL),
+        "passwd"
+    );
+    assert_eq!(
+        resolve(Some("filename=\"a<b>:c.txt\""), URL, URL),
+        "a_b__c.txt"
+    );
+    assert_eq!(
+        resolve(Some("filename=\"..\""), "https://example.com/", URL),
+        "tool v1.tar.gz"
+    );
+    assert_eq!(
+        resolve(None, "https://example.com", "https://example.com/"),
+        "download"
+    );
+}
@@ -0,0 +1,154 @@
+use std::{
+    fs,
+    sync::{Arc, Mutex},
+};
+
+use sha1::Sha1;
+use tempfile::tempdir;
+
+use crate::{Downloader, File, hash::Hash, indicator::silent::SilentFactory, plan::Action};
+
+use super::{init_tracing, response, serve};
+
+#[test]
+fn plan_batch() {
+    init_tracing();
+    let url = serve(1, |_| response("200 OK", &[], b"hello world!"));
+    let dir = tempdir().unwrap();
+    let current = dir.path().join("current.txt");
+    let other = dir.path().join("other.txt");
+    fs::write(&current, b"hello world!").unwrap();
+    fs::write(&other, b"old content").unwrap();
+
+    let downloader = Downloader::new(SilentFactory::new()).with_files(vec![
+        File::new(&format!("{url}/current.txt"))
+            .with_path(&current)
+            .with_hash(Hash::new::<Sha1>(
+                "430ce34d020724ed75a196dfc2ad67c77772d169",
+            )),
+        File::new(&format!("{url}/other.txt")).with_path(&other),
+        File::new(&format!("{url}/other.txt"))
+            .with_path(&other)
+            .with_size(100),
+    ]);
+    let plan = downloader.plan(true);
+
+    assert_eq!(plan.files[0].action, Action::Skip);
+    assert_eq!(plan.files[1].action, Action::Download);
+    assert_eq!(plan.files[1].available, Some(true));
+    assert_eq!(plan.files[1].size, Some(12));
+    assert!(plan.files[1].overwrite);
+    // The size is declared, no HEAD request is issued
+    assert_eq!(plan.files[2].available, None);
+    assert_eq!(plan.total_bytes, 112);
+    assert_eq!(plan.collisions, vec![other.clone()]);
+    // Nothing was written
+    assert_eq!(fs::read_to_string(&other).unwrap(), "old content");
+}
+
+#[test]
+fn plan_resolves_checksum_files() {
+    init_tracing();
+    // A single request, the checksum file is fetched once
+    let url = serve(1, |_| {
+        response(
+            "200 OK",
+            &[],
+            b"7509e5bda0c762d2bac7f90d758b5b2263fa01ccbc542ab5e3df163be08e6ca9  hello.txt\n",
+        )
+    });
+    let dir = tempdir().unwrap();
+    let current = dir.path().join("hello.txt");
+    fs::write(&current, b"hello world!").unwrap();
+
+    let plan = Downloader::new(SilentFactory::new())
+        .with_checksum_url(&format!("{url}/SHA256SUMS"))
+        .with_files(vec![
+            File::new(&format!("{url}/hello.txt")).with_path(&current),
+            File::new(&format!("{url}/missing.txt")).with_path(dir.path().join("missing.txt")),
+        ])
+        .plan(false);
+    assert_eq!(plan.files[0].action, Action::Skip);
+    assert_eq!(plan.files[1].action, Action::Download);
+}
+
+#[test]
+fn space_check_reuses_the_requests() {
+    init_tracing();
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let log = requests.clone();
+    let url = serve(3, move |head| {
+        log.lock()
+            .unwrap()
+            .push(head.lines().next().unwrap().to_string());
+        match head.starts_with("GET /SHA256SUMS") {
+            true => response(
+                "200 OK",
+                &[],
+                b"7509e5bda0c762d2bac7f90d758b5b2263fa01ccbc542ab5e3df163be08e6ca9  hello.txt\n",
+            ),
+            false => response("200 OK", &[], b"hello world!"),
+        }
+    });
+    let dir = tempdir().unwrap();
+    Downloader::new(SilentFactory::new())
+        .with_space_check(true)
+        .with_checksum_url(&format!("{url}/SHA256SUMS"))
+        .with_file(
+            File::new(&format!("{url}/hello.txt"))
+                .with_path(dir.path().join("hello.txt"))
+                .with_size(12),
+        )
+        .start()
+        .unwrap();
+    // The checksum file is fetched once and the declared size skips the HEAD request
+    assert_eq!(
+        *requests.lock().unwrap(),
+        vec!["GET /SHA256SUMS HTTP/1.1", "GET /hello.txt HTTP/1.1"]
+    );
+}
+
+#[test]
+fn space_check_fails_fast() {
+    init_tracing();
+    let url = serve(1, |_| response("200 OK", &[], b"hello world!"));
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("huge.img");
+    let result = Downloader::new(SilentFactory::new())
+        .with_space_check(true)
+        .with_file(File::new(&url).with_path(&path).with_size(u64::MAX / 2))
+        .start();
+
+    assert!(
+        result
+            .unwrap_err()
+            .to_string()
+            .starts_with("Not enough space")
+    );
+    assert!(!path.exists());
+}
+
+#[test]
+fn space_check_accounts_the_staging() {
+    init_tracing();
+    let dir = tempdir().unwrap();
+    let available = fs4::available_space(dir.path()).unwrap();
+    let file = |name: &str| {
+        File::new("http://127.0.0.1:9/file.img")
+            .with_path(dir.path().join(name))
+            .with_size(available / 10 * 4)
+    };
+    // In the same filesystem the staged file is renamed, it's only counted once
+    let downloader = Downloader::new(SilentFactory::new())
+        .with_transaction(true)
+        .with_staging_dir(dir.path().join("staging"))
+        .with_file(file("a.img"));
+    assert!(downloader.check_space().is_ok());
+    let downloader = downloader.with_file(file("b.img")).with_file(file("c.img"));
+    assert!(
+        downloader
+            .check_space()
+            .unwrap_err()
+            .starts_with("Not enough space")
+    );
+}
@@ -0,0 +1,102 @@
+use crate::signature::{PublicKey, Signature};
+#[cfg(feature = "ed25519")]
+use crate::{Downloader, File, error::FileError, indicator::silent::SilentFactory};
+#[cfg(feature = "ed25519")]
+use ed25519_dalek::{Signer, SigningKey};
+#[cfg(feature = "ed25519")]
+use tempfile::tempdir;
+
+#[cfg(feature = "ed25519")]
+use super::{init_tracing, response, serve};
+
+#[cfg(feature = "minisign")]
+#[test]
+fn minisign_signature() {
+    let key = "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3";
+    let signature = "untrusted comment: signature from minisign secret key
+RUQf6LRCGA9i559r3g7V1qNyJDApGip8MfqcadIgT9CuhV3EMhHoN1mGTkUidF/z7SrlQgXdy8ofjb7bNJJylDOocrCo8KLzZwo=
+trusted comment: timestamp:1556193335\tfile:test
+y/rUw2y8/hOUYjZU71eHp/Wo1KZ40fGy2VJEDl34XMJM+TX48Ss/17u3IvIfbVR1FkZZSNCisQbuQY+bHwhEBg==";
+    let check = Signature {
+        source: signature.into(),
+        key: PublicKey::Minisign(key.to_string()),
+    };
+    assert!(
+        check
+            .verify(signature.as_bytes(), b"test".as_slice())
+            .is_ok()
+    );
+    assert!(
+        check
+            .verify(signature.as_bytes(), b"tset".as_slice())
+            .is_err()
+    );
+}
+
+#[cfg(feature = "ed25519")]
+#[test]
+fn signify_signature() {
+    use base64::{Engine, engine::general_purpose::STANDARD};
+    let signer = SigningKey::from_bytes(&[7; 32]);
+    let key_id = [1, 2, 3, 4, 5, 6, 7, 8];
+    let encode = |content: &[u8]| {
+        let bytes = [b"Ed".as_slice(), &key_id, content].concat();
+        format!("untrusted comment: test\n{}\n", STANDARD.encode(bytes))
+    };
+    let key = encode(signer.verifying_key().as_bytes());
+    let signature = encode(&signer.sign(b"hello world!").to_bytes());
+    let check = Signature {
+        source: Vec::new().into(),
+        key: PublicKey::Signify(key),
+    };
+    assert!(
+        check
+            .verify(signature.as_bytes(), b"hello world!".as_slice())
+            .is_ok()
+    );
+    assert!(
+        check
+            .verify(signature.as_bytes(), b"hello world?".as_slice())
+            .is_err()
+    );
+}
+
+#[cfg(feature = "ed25519")]
+#[test]
+fn download_with_signature() {
+    init_tracing();
+    let signer = SigningKey::from_bytes(&[7; 32]);
+    let key = signer.verifying_key().to_bytes();
+    let signature = hex::encode(signer.sign(b"hello world!").to_bytes());
+    let served = signature.clone();
+    let url = serve(3, move |head| {
+        if head.starts_with("GET /hello.txt.sig") {
+            response("200 OK", &[], served.as_bytes())
+        } else {
+            response("200 OK", &[], b"hello world!")
+        }
+    });
+    let dir = tempdir().unwrap();
+    let downloaded = Downloader::new(SilentFactory::new())
+        .with_file(
+            File::new(&format!("{url}/hello.txt"))
+                .with_path(dir.path().join("hello.txt"))
+                .with_signature(format!("{url}/hello.txt.sig"), PublicKey::Ed25519(key)),
+        )
+        .start()
+        .unwrap();
+    assert!(downloaded[0].path.exists());
+
+    // The signature of other content is refused and the file removed
+    let other = signer.sign(b"other").to_bytes().to_vec();
+    let err = Downloader::new(SilentFactory::new())
+        .with_file(
+            File::new(&format!("{url}/world.txt"))
+                .with_path(dir.path().join("world.txt"))
+                .with_signature(other, PublicKey::Ed25519(key)),
+        )
+        .start()
+        .unwrap_err();
+    assert!(matches!(err.failures[0].1, FileError::Signature(_)));
+    assert!(!dir.path().join("world.txt").exists());
+}
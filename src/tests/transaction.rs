@@ -0,0 +1,103 @@
+use std::{fs, sync::Arc};
+
+use tempfile::tempdir;
+
+use crate::{
+    Decompression, Downloader, File,
+    cas::{LinkMode, default::DefaultStore},
+    decompress::tar::TarFactory,
+    indicator::silent::SilentFactory,
+};
+
+use super::{init_tracing, response, serve, tar};
+
+#[test]
+fn transaction_commits_the_batch() {
+    init_tracing();
+    let archive = tar();
+    let url = serve(2, move |_| response("200 OK", &[], &archive));
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("files/archive.tar");
+    let dst = dir.path().join("out");
+
+    let downloaded = Downloader::new(SilentFactory::new())
+        .with_transaction(true)
+        .with_staging_dir(dir.path())
+        .with_files(vec![
+            File::new(&format!("{url}/archive.tar"))
+                .with_path(&path)
+                .with_decompression(Decompression::new::<TarFactory>().with_dst(&dst)),
+            File::new(&format!("{url}/other.tar")).with_path(dir.path().join("other.tar")),
+        ])
+        .start()
+        .unwrap();
+
+    assert_eq!(downloaded[0].path, path);
+    assert!(path.exists());
+    assert_eq!(
+        fs::read_to_string(dst.join("hello.txt")).unwrap(),
+        "Hello, world!"
+    );
+    // The staging area was removed
+    assert!(fs::read_dir(dir.path()).unwrap().all(|e| {
+        !e.unwrap()
+            .file_name()
+            .to_string_lossy()
+            .starts_with(".dwutil")
+    }));
+}
+
+#[test]
+fn transaction_rolls_back_the_batch() {
+    init_tracing();
+    let archive = tar();
+    let url = serve(2, move |head| {
+        if head.starts_with("GET /missing") {
+            response("404 Not Found", &[], b"")
+        } else {
+            response("200 OK", &[], &archive)
+        }
+    });
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("archive.tar");
+    let dst = dir.path().join("out");
+    fs::write(&path, b"previous").unwrap();
+
+    let result = Downloader::new(SilentFactory::new())
+        .with_transaction(true)
+        .with_files(vec![
+            File::new(&format!("{url}/archive.tar"))
+                .with_path(&path)
+                .with_decompression(Decompression::new::<TarFactory>().with_dst(&dst)),
+            File::new(&format!("{url}/missing")).with_path(dir.path().join("missing")),
+        ])
+        .start();
+
+    assert!(result.is_err());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "previous");
+    assert!(!dst.exists());
+    assert!(!dir.path().join("missing").exists());
+}
+
+#[test]
+fn transaction_keeps_relative_symlinks() {
+    init_tracing();
+    let url = serve(1, |_| response("200 OK", &[], b"hello world!"));
+    let dir = tempdir().unwrap();
+    let store = Arc::new(DefaultStore::new(dir.path().join("objects")));
+    let path = dir.path().join("files/hello.txt");
+
+    Downloader::new(SilentFactory::new())
+        .with_transaction(true)
+        .with_staging_dir(dir.path().join("staging"))
+        .with_file(
+            File::new(&format!("{url}/hello.txt"))
+                .with_path(&path)
+                .with_store(store)
+                .with_link_mode(LinkMode::RelativeSymlink),
+        )
+        .start()
+        .unwrap();
+    assert!(fs::read_link(&path).unwrap().is_relative());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "hello world!");
+}
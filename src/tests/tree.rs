@@ -0,0 +1,147 @@
+use std::{fs, io::Write};
+
+use sha2::Sha256;
+use tempfile::tempdir;
+
+use super::tar;
+use crate::{
+    Decompression,
+    hash::{
+        Hash, Hasher,
+        tree::{EntryKind, Tree, TreeChange},
+    },
+};
+
+fn sha256() -> Box<dyn Hasher> {
+    Box::new(<Sha256 as Hasher>::new())
+}
+
+#[test]
+fn tree_manifest_and_digest() {
+    let dir = tempdir().unwrap();
+    fs::create_dir(dir.path().join("bin")).unwrap();
+    fs::write(dir.path().join("bin/tool"), b"hello world!").unwrap();
+    fs::write(dir.path().join("a\tb.txt"), b"").unwrap();
+
+    let tree = Tree::compute(dir.path(), sha256).unwrap();
+    assert_eq!(
+        tree.manifest(),
+        "file\te3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855\ta\\tb.txt\n\
+         dir\t-\tbin\n\
+         file\t7509e5bda0c762d2bac7f90d758b5b2263fa01ccbc542ab5e3df163be08e6ca9\tbin/tool\n"
+    );
+    assert_eq!(Tree::parse(&tree.manifest()).unwrap(), tree);
+
+    let digest = tree.digest(sha256).to_hex();
+    let hash = Hash::new::<Sha256>(&digest);
+    assert_eq!(hash.check_tree(dir.path()).unwrap(), Some(()));
+
+    fs::write(dir.path().join("bin/tool"), b"hello world?").unwrap();
+    assert_eq!(hash.check_tree(dir.path()).unwrap(), None);
+}
+
+#[test]
+fn tree_drift() {
+    let dir = tempdir().unwrap();
+    fs::write(dir.path().join("kept"), b"kept").unwrap();
+    fs::write(dir.path().join("removed"), b"removed").unwrap();
+    fs::write(dir.path().join("modified"), b"modified").unwrap();
+    let old = Tree::compute(dir.path(), sha256).unwrap();
+
+    fs::remove_file(dir.path().join("removed")).unwrap();
+    fs::write(dir.path().join("modified"), b"tampered").unwrap();
+    fs::write(dir.path().join("added"), b"added").unwrap();
+    let new = Tree::compute(dir.path(), sha256).unwrap();
+
+    assert_eq!(
+        old.diff(&new),
+        vec![
+            TreeChange::Added("added".to_string()),
+            TreeChange::Modified("modified".to_string()),
+            TreeChange::Removed("removed".to_string()),
+        ]
+    );
+    assert!(new.diff(&new).is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn tree_modes_and_symlinks() {
+    use std::os::unix::fs::PermissionsExt;
+    let dir = tempdir().unwrap();
+    let tool = dir.path().join("tool");
+    fs::write(&tool, b"#!/bin/sh").unwrap();
+    std::os::unix::fs::symlink("tool", dir.path().join("link")).unwrap();
+    let old = Tree::compute(dir.path(), sha256).unwrap();
+    assert_eq!(
+        old.entries[0].kind,
+        EntryKind::Symlink {
+            target: "tool".to_string()
+        }
+    );
+
+    fs::set_permissions(&tool, fs::Permissions::from_mode(0o755)).unwrap();
+    let new = Tree::compute(dir.path(), sha256).unwrap();
+    assert!(matches!(
+        new.entries[1].kind,
+        EntryKind::File {
+            executable: true,
+            ..
+        }
+    ));
+    assert_eq!(
+        old.diff(&new),
+        vec![TreeChange::Modified("tool".to_string())]
+    );
+}
+
+#[test]
+fn extraction_tree_hash() {
+    let tmp = tempdir().unwrap();
+    let bytes = tar();
+
+    let expected = tempdir().unwrap();
+    fs::File::create(expected.path().join("hello.txt"))
+        .unwrap()
+        .write_all(b"Hello, world!")
+        .unwrap();
+    let digest = Tree::compute(expected.path(), sha256)
+        .unwrap()
+        .digest(sha256)
+        .to_hex();
+
+    // Only the extraction is hashed, the other files of the destination are ignored
+    let dst = tmp.path().join("out");
+    fs::create_dir(&dst).unwrap();
+    fs::write(dst.join("other.txt"), b"other").unwrap();
+    Decompression::new::<crate::decompress::tar::TarFactory>()
+        .with_dst(&dst)
+        .with_tree_hash(Hash::new::<Sha256>(&digest))
+        .extract(bytes.clone())
+        .unwrap();
+    assert!(dst.join("hello.txt").exists());
+
+    // The excluded files aren't hashed
+    let empty = Tree::compute(tempdir().unwrap().path(), sha256)
+        .unwrap()
+        .digest(sha256)
+        .to_hex();
+    let excluded = tmp.path().join("excluded");
+    Decompression::new::<crate::decompress::tar::TarFactory>()
+        .with_dst(&excluded)
+        .with_exclude("/hello.txt")
+        .with_tree_hash(Hash::new::<Sha256>(&empty))
+        .extract(bytes.clone())
+        .unwrap();
+    assert!(!excluded.join("hello.txt").exists());
+
+    // A mismatch doesn't write the destination
+    let other = tmp.path().join("other");
+    let err = Decompression::new::<crate::decompress::tar::TarFactory>()
+        .with_dst(&other)
+        .with_tree_hash(Hash::new::<Sha256>(&"0".repeat(64)))
+        .extract(bytes)
+        .unwrap_err();
+    assert!(err.contains("Tree hash"));
+    assert!(!other.exists());
+}
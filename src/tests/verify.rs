@@ -0,0 +1,102 @@
+use std::{
+    fs,
+    sync::{Arc, Mutex},
+};
+
+use sha2::Sha256;
+use tempfile::tempdir;
+
+use crate::{
+    checksum,
+    hash::{
+        Hash,
+        verify::{Verdict, Verifier},
+    },
+    indicator::{Indicator, IndicatorFactory, silent::SilentFactory},
+};
+
+const HELLO: &str = "7509e5bda0c762d2bac7f90d758b5b2263fa01ccbc542ab5e3df163be08e6ca9";
+
+/// Records the last progress of every indicator
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Vec<(String, u64, bool)>>>);
+impl IndicatorFactory for Recorder {
+    fn create(&mut self, name: String, _: usize) -> Box<dyn Indicator + Send> {
+        let mut records = self.0.lock().unwrap();
+        records.push((name, 0, false));
+        Box::new(RecorderIndicator(self.0.clone(), records.len() - 1))
+    }
+}
+struct RecorderIndicator(Arc<Mutex<Vec<(String, u64, bool)>>>, usize);
+impl Indicator for RecorderIndicator {
+    fn update(&mut self, bytes: u64) {
+        self.0.lock().unwrap()[self.1].1 = bytes;
+    }
+    fn error(&mut self, _: String) {}
+    fn stage(&mut self, _: String) {}
+    fn end(&mut self) {
+        self.0.lock().unwrap()[self.1].2 = true;
+    }
+}
+
+#[test]
+fn verify_files() {
+    let dir = tempdir().unwrap();
+    for i in 0..8 {
+        fs::write(dir.path().join(format!("{i}.txt")), b"hello world!").unwrap();
+    }
+    fs::write(dir.path().join("bad.txt"), b"hello world?").unwrap();
+
+    let mut verifier = Verifier::new(SilentFactory::new()).with_threads(3);
+    for i in 0..8 {
+        verifier = verifier.with_file(
+            dir.path().join(format!("{i}.txt")),
+            Hash::new::<Sha256>(HELLO),
+        );
+    }
+    let verified = verifier
+        .with_file(dir.path().join("bad.txt"), Hash::new::<Sha256>(HELLO))
+        .with_file(dir.path().join("missing.txt"), Hash::new::<Sha256>(HELLO))
+        .start();
+
+    assert_eq!(verified.len(), 10);
+    assert!(verified[..8].iter().all(|file| file.passed()));
+    assert_eq!(verified[3].path, dir.path().join("3.txt"));
+    assert_eq!(verified[0].digests[0].to_hex(), HELLO);
+    assert!(matches!(&verified[8].verdict, Verdict::Failed { expected, .. } if expected == HELLO));
+    assert_eq!(verified[9].verdict, Verdict::Missing);
+}
+
+#[test]
+fn verify_checksum_manifest() {
+    let dir = tempdir().unwrap();
+    let big = vec![7u8; 3 * 1024 * 1024 + 5];
+    fs::write(dir.path().join("big.bin"), &big).unwrap();
+    fs::write(dir.path().join("hello.txt"), b"hello world!").unwrap();
+    let sums = format!("{}  big.bin\n{HELLO}  hello.txt\n", sha256(&big));
+    let entries = checksum::parse(&sums, "SHA256SUMS");
+
+    let recorder = Recorder::default();
+    let verified = Verifier::new(recorder.clone())
+        .with_checksums(dir.path(), &entries)
+        .unwrap()
+        // The big file is mapped when the feature is enabled
+        .with_mmap_threshold(1024 * 1024)
+        .start();
+    assert!(verified.iter().all(|file| file.passed()));
+
+    let mut records = recorder.0.lock().unwrap().clone();
+    records.sort();
+    assert_eq!(
+        records,
+        vec![
+            ("big.bin".to_string(), big.len() as u64, true),
+            ("hello.txt".to_string(), 12, true),
+        ]
+    );
+}
+
+fn sha256(bytes: &[u8]) -> String {
+    use crate::hash::Hasher;
+    <Sha256 as Hasher>::compute(bytes).unwrap()
+}
@@ -0,0 +1,186 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use tempfile::TempDir;
+use tracing::{debug, warn};
+
+use crate::{
+    Downloaded, File,
+    cas::{self, LinkMode},
+    utils,
+};
+
+/// Staging area of a transactional batch, the staged outputs are moved
+/// to the final paths only when the whole batch succeeded.
+/// Dropping the staging without commit rolls back the batch
+pub(crate) struct Staging {
+    /// Staging directory
+    dir: TempDir,
+    /// Staged outputs and the final paths
+    moves: Vec<(PathBuf, PathBuf)>,
+}
+impl Staging {
+    /// Redirect the outputs of the files to the staging area
+    pub fn stage(files: &mut [File], dir: Option<&Path>) -> Result<Self, String> {
+        let builder = tempfile::Builder::new().prefix(".dwutil-staging-").clone();
+        let dir = match dir {
+            Some(dir) => {
+                fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+                builder.tempdir_in(dir)
+            }
+            None => builder.tempdir(),
+        }
+        .map_err(|e| e.to_string())?;
+        let mut moves = Vec::new();
+        for (index, file) in files.iter_mut().enumerate() {
+            let staged = dir.path().join(index.to_string());
+            // The files that already match the hash are not written
+            if !file.path.as_os_str().is_empty() && !file.is_current() {
+                let name = file.path.file_name().unwrap_or(file.path.as_os_str());
+                let path = staged.join(name);
+                moves.push((path.clone(), file.path.clone()));
+                file.staged_from = Some(std::mem::replace(&mut file.path, path));
+            }
+            if let Some(dir) = &file.dir {
+                let staged = staged.join("dir");
+                moves.push((staged.clone(), dir.clone()));
+                file.staged_dir = Some(staged);
+            }
+            if let Some(decompression) = &mut file.decompression {
+                let dst = staged.join("extract");
+                moves.push((dst.clone(), decompression.dst.clone()));
+                decompression.dst = dst;
+            }
+        }
+        debug!("Staging the batch in {}", dir.path().to_string_lossy());
+        Ok(Self { dir, moves })
+    }
+    /// Move the staged outputs to the final paths, if a move fails
+    /// the already commited outputs are restored
+    pub fn commit(self, downloaded: &mut [Downloaded]) -> Result<(), String> {
+        let backup = self.dir.path().join("backup");
+        let mut journal = Journal::default();
+        for (staged, dst) in &self.moves {
+            // The staged output may not exist, as a file streamed to a sink
+            if staged.symlink_metadata().is_err() {
+                continue;
+            }
+            if let Err(err) = journal.commit(staged, dst, &backup) {
+                warn!("Failed commiting the batch, rolling back -- {err}");
+                journal.rollback();
+                return Err(format!(
+                    "Failed commiting {}: {}",
+                    dst.to_string_lossy(),
+                    err
+                ));
+            }
+        }
+        for file in downloaded.iter_mut() {
+            for (staged, dst) in &self.moves {
+                if let Ok(relative) = file.path.strip_prefix(staged) {
+                    file.path = match relative.as_os_str().is_empty() {
+                        true => dst.clone(),
+                        false => dst.join(relative),
+                    };
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Record of the commited changes, used to rollback
+#[derive(Default)]
+struct Journal {
+    /// Created directories
+    dirs: Vec<PathBuf>,
+    /// Commited paths and the backup of the replaced path
+    paths: Vec<(PathBuf, Option<PathBuf>)>,
+}
+impl Journal {
+    /// Commit a staged file or directory tree
+    fn commit(&mut self, staged: &Path, dst: &Path, backup: &Path) -> io::Result<()> {
+        let metadata = staged.symlink_metadata()?;
+        if metadata.is_dir() {
+            self.create_dir(dst)?;
+            for entry in fs::read_dir(staged)? {
+                let entry = entry?;
+                self.commit(&entry.path(), &dst.join(entry.file_name()), backup)?;
+            }
+            return Ok(());
+        }
+        if let Some(parent) = dst.parent() {
+            self.create_dir(parent)?;
+        }
+        // Keep the replaced file to restore it in the rollback
+        let mut saved = None;
+        if let Ok(metadata) = dst.symlink_metadata() {
+            if metadata.is_dir() {
+                return Err(io::Error::other("A directory exists in the path"));
+            }
+            let path = backup.join(self.paths.len().to_string());
+            fs::create_dir_all(backup)?;
+            move_path(dst, &path)?;
+            saved = Some(path);
+        }
+        let moved = move_path(staged, dst);
+        self.paths.push((dst.to_path_buf(), saved));
+        moved
+    }
+    /// Create a directory and the missing parents
+    fn create_dir(&mut self, dir: &Path) -> io::Result<()> {
+        let mut missing: Vec<&Path> = dir.ancestors().take_while(|p| !p.exists()).collect();
+        missing.reverse();
+        for dir in missing {
+            if dir.as_os_str().is_empty() {
+                continue;
+            }
+            fs::create_dir(dir)?;
+            self.dirs.push(dir.to_path_buf());
+        }
+        Ok(())
+    }
+    /// Restore the replaced paths and remove the created directories
+    fn rollback(self) {
+        for (path, saved) in self.paths.into_iter().rev() {
+            if path.symlink_metadata().is_ok() && fs::remove_file(&path).is_err() {
+                warn!("Failed removing {}", path.to_string_lossy());
+            }
+            if let Some(saved) = saved
+                && move_path(&saved, &path).is_err()
+            {
+                warn!("Failed restoring {}", path.to_string_lossy());
+            }
+        }
+        for dir in self.dirs.into_iter().rev() {
+            if fs::remove_dir(&dir).is_err() {
+                warn!("Failed removing {}", dir.to_string_lossy());
+            }
+        }
+    }
+}
+
+/// Move a file or symlink, copy it if the rename fails (as between filesystems).
+/// The relative symlinks are relinked from the new path
+fn move_path(src: &Path, dst: &Path) -> io::Result<()> {
+    if src.symlink_metadata()?.is_symlink()
+        && let target = fs::read_link(src)?
+        && target.is_relative()
+    {
+        let target = src.parent().unwrap_or(Path::new("")).join(target);
+        cas::link(&target, dst, LinkMode::RelativeSymlink).map_err(io::Error::other)?;
+        return fs::remove_file(src);
+    }
+    if fs::rename(src, dst).is_ok() {
+        return Ok(());
+    }
+    if src.symlink_metadata()?.is_symlink() {
+        utils::create_symlink(fs::read_link(src)?, dst)?;
+    } else {
+        fs::copy(src, dst)?;
+    }
+    fs::remove_file(src)
+}